@@ -21,5 +21,6 @@ cargo build --release --target x86_64-pc-windows-gnu
 
 echo "Copying binaries"
 cp target/x86_64-unknown-linux-gnu/release/openbook-desktop build/
+cp target/x86_64-unknown-linux-gnu/release/libflutter_engine.so build/
 cp target/x86_64-pc-windows-gnu/release/openbook-desktop.exe build/
 cp target/x86_64-pc-windows-gnu/release/flutter_engine.dll build/
@@ -1,8 +1,12 @@
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use cargo_toml::Manifest;
 use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
 
 #[derive(Deserialize)]
 struct MetaData {
@@ -12,6 +16,12 @@ struct MetaData {
 #[derive(Deserialize)]
 struct FlutterMetadata {
     version: String,
+    /// SHA-256 checksums of the engine library by engine version and target.
+    #[serde(default)]
+    checksums: HashMap<String, HashMap<String, String>>,
+    /// App name and package name reported through package_info.
+    app_name: Option<String>,
+    package_name: Option<String>,
 }
 
 enum Target {
@@ -20,6 +30,40 @@ enum Target {
     Windows,
 }
 
+/// Directory with an already downloaded flutter engine or a zip archive of one, used instead of
+/// downloading the engine.
+const ENGINE_PATH_ENV: &str = "FLUTTER_ENGINE_PATH";
+/// Set to `1` to use an engine that doesn't match the checksum pinned in `Cargo.toml`, e.g. a
+/// locally built one.
+const SKIP_CHECKSUM_ENV: &str = "FLUTTER_ENGINE_SKIP_CHECKSUM";
+
+impl Target {
+    fn engine_lib_name(&self) -> &'static str {
+        match self {
+            Target::Linux => "libflutter_engine.so",
+            Target::MacOS => "FlutterEmbedder.framework",
+            Target::Windows => "flutter_engine.dll",
+        }
+    }
+
+    fn checksum_key(&self) -> &'static str {
+        match self {
+            Target::Linux => "linux",
+            Target::MacOS => "macos",
+            Target::Windows => "windows",
+        }
+    }
+
+    /// The file that gets checksummed, relative to the engine directory. For the macOS
+    /// framework this is the library inside it.
+    fn checksum_file(&self) -> PathBuf {
+        match self {
+            Target::MacOS => Path::new(self.engine_lib_name()).join("FlutterEmbedder"),
+            _ => PathBuf::from(self.engine_lib_name()),
+        }
+    }
+}
+
 fn main() {
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("Cannot get project dir");
     let project_path = Path::new(&manifest_dir);
@@ -28,13 +72,20 @@ fn main() {
         &fs::read(&toml_path).expect("Cannot read Cargo.toml"),
     )
     .expect("Cannot parse Cargo.toml");
-    let version = manifest
+    let flutter = manifest
         .package
         .expect("Flutter config missing in Cargo.toml")
         .metadata
         .expect("Flutter config missing in Cargo.toml")
-        .flutter
-        .version;
+        .flutter;
+    let version = flutter.version;
+    println!("cargo:rustc-env=FLUTTER_ENGINE_VERSION={}", version);
+    embed_git_revisions(project_path);
+    embed_app_info(
+        project_path,
+        flutter.app_name.as_ref().map(String::as_str),
+        flutter.package_name.as_ref().map(String::as_str),
+    );
 
     let libs_dir = project_path.join("libs");
 
@@ -53,58 +104,341 @@ fn main() {
         panic!("Unknown target {}", target)
     };
 
+    let libs_dir = match prepare_engine(&version, &libs_dir, &target) {
+        Ok(libs_dir) => libs_dir,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let checksum = flutter
+        .checksums
+        .get(&version)
+        .and_then(|checksums| checksums.get(target.checksum_key()));
+    if let Err(err) = verify_engine(&libs_dir, &target, checksum) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    // look for the engine next to the binary first so bundles work, then in the engine dir,
+    // which still resolves for `cargo install`ed binaries
+    let binary_dir = binary_dir();
+    let libs_path = libs_dir.to_str().expect("libs_dir invalid");
+    match target {
+        Target::Linux => {
+            println!("cargo:rustc-link-arg=-Wl,-rpath,$ORIGIN");
+            println!("cargo:rustc-link-arg=-Wl,-rpath,{}", libs_path);
+            stage_engine(binary_dir, &libs_dir.join(target.engine_lib_name()));
+            println!("cargo:rustc-link-search=native={}", libs_path);
+        }
+        Target::MacOS => {
+            println!("cargo:rustc-link-arg=-Wl,-rpath,@loader_path");
+            println!("cargo:rustc-link-arg=-Wl,-rpath,{}", libs_path);
+            stage_engine(binary_dir, &libs_dir.join(target.engine_lib_name()));
+            println!("cargo:rustc-link-search=framework={}", libs_path);
+        }
+        Target::Windows => {
+            // windows does not use rpath, but it looks next to the binary as well
+            stage_engine(binary_dir, &libs_dir.join(target.engine_lib_name()));
+            println!("cargo:rustc-link-search=native={}", libs_path);
+
+            //            let mut res = winres::WindowsResource::new();
+            //            res.set_icon_with_id("./assets/icon.ico", "GLFW_ICON");
+            //            res.compile().unwrap();
+        }
+    };
+}
+
+/// Finds the directory cargo puts the final binaries in. `OUT_DIR` is always
+/// `<binary dir>/build/<package>-<hash>/out`, no matter where the target dir is.
+fn binary_dir() -> Option<PathBuf> {
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR")?);
+    out_dir
+        .ancestors()
+        .find(|dir| dir.file_name().map_or(false, |name| name == "build"))
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+}
+
+fn copy_recursively(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Copies the engine library next to the final binary and the test binaries, so the build
+/// directory can be run or bundled as is.
+fn stage_engine(binary_dir: Option<PathBuf>, lib: &Path) {
+    let dir = match binary_dir {
+        Some(dir) => dir,
+        None => {
+            println!(
+                "cargo:warning=Cannot find the binary dir, copy {} next to the binary yourself",
+                lib.display()
+            );
+            return;
+        }
+    };
+    let name = lib.file_name().expect("Invalid engine library path");
+    for dir in vec![dir.join("deps"), dir] {
+        if let Err(err) =
+            fs::create_dir_all(&dir).and_then(|_| copy_recursively(lib, &dir.join(name)))
+        {
+            println!(
+                "cargo:warning=Cannot copy {} to {}: {}",
+                lib.display(),
+                dir.display(),
+                err
+            );
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(String::from(output.trim())).filter(|output| !output.is_empty())
+}
+
+/// Embeds the git commit of this repo and of the openbook-app submodule. Builds from a source
+/// archive without git get `unknown`.
+fn embed_git_revisions(project_path: &Path) {
+    let head = git(project_path, &["rev-parse", "HEAD"]);
+    // the submodule may not be checked out, the revision recorded in the index is still known
+    let app_revision = git(&project_path.join("openbook-app"), &["rev-parse", "HEAD"])
+        .filter(|revision| Some(revision) != head.as_ref())
+        .or_else(|| {
+            git(project_path, &["ls-files", "--stage", "openbook-app"])
+                .and_then(|line| line.split_whitespace().nth(1).map(String::from))
+        });
+    println!(
+        "cargo:rustc-env=GIT_COMMIT={}",
+        head.clone().unwrap_or_else(|| String::from("unknown"))
+    );
+    println!(
+        "cargo:rustc-env=OPENBOOK_APP_REVISION={}",
+        app_revision.unwrap_or_else(|| String::from("unknown"))
+    );
+
+    // only commits and checkouts change these. Watching the index or the sources would rerun
+    // this script, which hashes and copies the engine, on every edit.
+    watch_git_head(project_path);
+    watch_git_head(&project_path.join("openbook-app"));
+}
+
+/// Reruns the build script when the checked out commit of the repository at `dir` changes.
+fn watch_git_head(dir: &Path) {
+    let git_dir = match git(dir, &["rev-parse", "--git-dir"]) {
+        Some(git_dir) => dir.join(git_dir),
+        None => return,
+    };
+    let mut files = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    // the branch that's checked out, commits only change its ref
+    if let Some(reference) = git(dir, &["symbolic-ref", "-q", "HEAD"]) {
+        files.push(git_dir.join(reference));
+    }
+    for file in files.iter().filter(|file| file.exists()) {
+        println!("cargo:rerun-if-changed={}", file.display());
+    }
+}
+
+/// Embeds the app name and package name from the metadata and the app version from the
+/// openbook-app `pubspec.yaml`.
+fn embed_app_info(project_path: &Path, app_name: Option<&str>, package_name: Option<&str>) {
+    let pubspec = project_path.join("openbook-app").join("pubspec.yaml");
+    let app_version = fs::read_to_string(&pubspec).ok().and_then(|pubspec| {
+        pubspec
+            .lines()
+            .find(|line| line.starts_with("version:"))
+            .map(|line| String::from(line["version:".len()..].trim()))
+    });
+    if pubspec.exists() {
+        println!("cargo:rerun-if-changed=openbook-app/pubspec.yaml");
+    }
+    let package = std::env::var("CARGO_PKG_NAME").expect("Cannot get package name");
+    println!(
+        "cargo:rustc-env=PACKAGE_INFO_APP_NAME={}",
+        app_name.unwrap_or(&package)
+    );
+    println!(
+        "cargo:rustc-env=PACKAGE_INFO_PACKAGE_NAME={}",
+        package_name.unwrap_or(&package)
+    );
+    println!(
+        "cargo:rustc-env=OPENBOOK_APP_VERSION={}",
+        app_version.unwrap_or_else(|| String::from("unknown"))
+    );
+}
+
+/// Finds the flutter engine for the given version, either from `FLUTTER_ENGINE_PATH`, from the
+/// `libs/<version>` cache or by downloading it. Returns the directory containing the engine.
+fn prepare_engine(version: &str, libs_dir: &Path, target: &Target) -> Result<PathBuf, String> {
+    println!("cargo:rerun-if-env-changed={}", ENGINE_PATH_ENV);
+    // the engine version comes from Cargo.toml
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=build.rs");
+    let lib_name = target.engine_lib_name();
+    let cache_dir = libs_dir.join(version);
+
+    if let Some(path) = std::env::var_os(ENGINE_PATH_ENV) {
+        let path = PathBuf::from(path);
+        let engine_dir = if path.is_dir() {
+            path.clone()
+        } else if path.is_file() {
+            println!("Extracting flutter engine from {}", path.display());
+            extract_archive(&path, &cache_dir, lib_name)
+                .map_err(|err| format!("Cannot extract {}: {}", path.display(), err))?;
+            cache_dir
+        } else {
+            return Err(format!(
+                "{} is set to {}, which doesn't exist",
+                ENGINE_PATH_ENV,
+                path.display()
+            ));
+        };
+        if !engine_dir.join(lib_name).exists() {
+            return Err(format!("{} doesn't contain {}", path.display(), lib_name));
+        }
+        return Ok(engine_dir);
+    }
+
+    if cache_dir.join(lib_name).exists() {
+        println!("Using cached flutter engine in {}", cache_dir.display());
+        return Ok(cache_dir);
+    }
+
     println!("Downloading flutter engine");
-    if let Ok(rx) = flutter_download::download_to(&version, &libs_dir) {
+    if let Ok(rx) = flutter_download::download_to(version, libs_dir) {
         for (total, done) in rx.iter() {
             println!("Downloading flutter engine {} of {}", done, total);
         }
     }
+    if !cache_dir.join(lib_name).exists() {
+        return Err(format!(
+            "Cannot download flutter engine {}. When building offline, set {} to a directory or \
+             zip archive containing {}, or put it into {}",
+            version,
+            ENGINE_PATH_ENV,
+            lib_name,
+            cache_dir.display()
+        ));
+    }
+    Ok(cache_dir)
+}
 
-    let libs_dir = libs_dir.join(&version);
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.result()))
+}
 
-    match target {
-        Target::Linux => {
-            let src = libs_dir.join("libflutter_engine.so");
-            let tar = Path::new(&std::env::var("OUT_DIR").unwrap())
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .join("libflutter_engine.so");
-            fs::copy(src, tar).expect("Cannot copy libflutter_engine.so");
+/// Checks the engine library against the checksum pinned in `Cargo.toml`. Without a pinned
+/// checksum there's only a warning with the actual one, so it can be pinned after checking it.
+fn verify_engine(
+    engine_dir: &Path,
+    target: &Target,
+    expected: Option<&String>,
+) -> Result<(), String> {
+    println!("cargo:rerun-if-env-changed={}", SKIP_CHECKSUM_ENV);
+    let path = engine_dir.join(target.checksum_file());
+    let actual = sha256(&path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+    let skip = std::env::var_os(SKIP_CHECKSUM_ENV).map_or(false, |skip| skip == "1");
+    match expected {
+        Some(expected) if expected.eq_ignore_ascii_case(&actual) => Ok(()),
+        None => {
             println!(
-                "cargo:rustc-link-search=native={}",
-                libs_dir.to_str().expect("libs_dir invalid")
+                "cargo:warning=No checksum for the {} flutter engine pinned in Cargo.toml, {} \
+                 wasn't verified. If it's the official engine, pin it with `{} = \"{}\"`.",
+                target.checksum_key(),
+                path.display(),
+                target.checksum_key(),
+                actual
             );
+            Ok(())
         }
-        Target::MacOS => println!(
-            "cargo:rustc-link-search=framework={}",
-            libs_dir.to_str().expect("libs_dir invalid")
-        ),
-        Target::Windows => {
-            // windows does not use rpath, we have to copy dll to OUT_DIR
-            let src = libs_dir.join("flutter_engine.dll");
-            let tar = Path::new(&std::env::var("OUT_DIR").unwrap())
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .parent()
-                .unwrap()
-                .join("flutter_engine.dll");
-            fs::copy(src, tar).expect("Cannot copy flutter_engine.dll");
+        Some(_) if skip => {
             println!(
-                "cargo:rustc-link-search=native={}",
-                libs_dir.to_str().expect("libs_dir invalid")
+                "cargo:warning=Not verifying the flutter engine because {} is set, {} has SHA-256 \
+                 {}",
+                SKIP_CHECKSUM_ENV,
+                path.display(),
+                actual
             );
+            Ok(())
+        }
+        Some(expected) => Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}. Delete it to download it again, or \
+             set {}=1 to build with it anyway.",
+            path.display(),
+            expected,
+            actual,
+            SKIP_CHECKSUM_ENV
+        )),
+    }
+}
 
-            //            let mut res = winres::WindowsResource::new();
-            //            res.set_icon_with_id("./assets/icon.ico", "GLFW_ICON");
-            //            res.compile().unwrap();
+/// Returns the directory all entries are in, if there is exactly one and it isn't `keep`.
+fn single_top_dir(names: &[PathBuf], keep: &str) -> Option<PathBuf> {
+    let mut tops = names.iter().filter_map(|name| name.components().next());
+    let top = tops.next()?;
+    let nested = names.iter().any(|name| name.components().count() > 1);
+    if nested && tops.all(|other| other == top) && top.as_os_str() != keep {
+        Some(PathBuf::from(top.as_os_str()))
+    } else {
+        None
+    }
+}
+
+/// Extracts a zip archive containing `lib_name`. A single directory wrapping everything, as
+/// most hand-made archives have, is stripped so the library ends up directly in `target_dir`.
+fn extract_archive(archive: &Path, target_dir: &Path, lib_name: &str) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(archive)?)?;
+    let names = (0..archive.len())
+        .map(|index| archive.by_index(index).map(|file| file.sanitized_name()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let prefix = single_top_dir(&names, lib_name);
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.sanitized_name();
+        let name = match &prefix {
+            Some(prefix) => name.strip_prefix(prefix).unwrap_or(&name).to_path_buf(),
+            None => name,
+        };
+        if name.as_os_str().is_empty() {
+            continue;
         }
-    };
+        let path = target_dir.join(name);
+        if file.name().ends_with('/') {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&path)?)?;
+    }
+    Ok(())
 }
 
 fn mingw_check_47048() {
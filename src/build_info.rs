@@ -0,0 +1,21 @@
+/// Version of openbook-desktop itself.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Flutter engine version from `package.metadata.flutter` in `Cargo.toml`.
+pub const ENGINE_VERSION: &str = env!("FLUTTER_ENGINE_VERSION");
+/// Commit this binary was built from.
+pub const GIT_COMMIT: &str = env!("GIT_COMMIT");
+/// Commit of the openbook-app submodule.
+pub const APP_REVISION: &str = env!("OPENBOOK_APP_REVISION");
+
+/// App name and package name from `package.metadata.flutter` in `Cargo.toml`.
+pub const APP_NAME: &str = env!("PACKAGE_INFO_APP_NAME");
+pub const PACKAGE_NAME: &str = env!("PACKAGE_INFO_PACKAGE_NAME");
+/// Version of the openbook-app from its `pubspec.yaml`, e.g. `0.0.46+46`.
+pub const APP_VERSION: &str = env!("OPENBOOK_APP_VERSION");
+
+pub fn summary() -> String {
+    format!(
+        "openbook-desktop {} (commit {}, openbook-app {}, flutter engine {})",
+        VERSION, GIT_COMMIT, APP_REVISION, ENGINE_VERSION
+    )
+}
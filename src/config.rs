@@ -0,0 +1,133 @@
+use std::fs::File;
+
+use serde_derive::Deserialize;
+
+use crate::profile::Profile;
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Settings read from `config.json` in the profile's config directory. Every value is optional,
+/// missing values use their defaults.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub logging: LoggingConfig,
+    pub sentry: SentryConfig,
+    pub url_launcher: UrlLauncherConfig,
+    pub images: ImagesConfig,
+    pub share: ShareConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Size in bytes after which the log file is rotated.
+    pub max_file_size: u64,
+    /// Number of compressed log files kept after rotating.
+    pub max_archives: usize,
+    /// Log filter in `RUST_LOG` format for all sinks.
+    pub filter: Option<String>,
+    pub stdout_filter: Option<String>,
+    pub file_filter: Option<String>,
+    pub stdout_format: LogFormat,
+    pub file_format: LogFormat,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines, colored on stdout.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 10 * 1024 * 1024,
+            max_archives: 7,
+            filter: None,
+            stdout_filter: None,
+            file_filter: None,
+            stdout_format: LogFormat::Text,
+            file_format: LogFormat::Text,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SentryConfig {
+    /// Capture Sentry reports locally instead of sending them to the app's DSN.
+    pub enabled: bool,
+    /// Port of the local endpoint, 0 picks a free one.
+    pub port: u16,
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: 0,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct UrlLauncherConfig {
+    /// Command and arguments to open URLs with, the URL is appended as the last argument. By
+    /// default `xdg-open` is used, falling back to the OpenURI desktop portal.
+    pub opener: Option<Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// Remove all EXIF metadata, including the GPS position, when images are normalized.
+    pub strip_metadata: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct ShareConfig {
+    /// Where shared text is sent besides the clipboard. Nothing else happens by default.
+    pub handler: Option<ShareHandler>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareHandler {
+    /// A new mail in the default mail program, through `xdg-email`.
+    Email,
+    /// A program and its arguments, the text is appended as the last argument.
+    Command(Vec<String>),
+}
+
+impl Config {
+    /// Loads the config of the given profile. This runs before logging is set up, so problems
+    /// are reported on stderr.
+    pub fn load(profile: &Profile) -> Self {
+        let path = match profile.config_dir() {
+            Some(dir) => dir.join(CONFIG_FILE_NAME),
+            None => return Self::default(),
+        };
+        match File::open(&path) {
+            Ok(file) => match serde_json::from_reader(file) {
+                Ok(config) => config,
+                Err(err) => {
+                    eprintln!("Cannot parse {}: {}", path.display(), err);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default(),
+        }
+    }
+}
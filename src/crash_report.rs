@@ -0,0 +1,217 @@
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::panic::{self, PanicInfo};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::thread;
+
+use backtrace::Backtrace;
+use log::warn;
+
+use crate::notification;
+
+use crate::{build_info, logging, profile::Profile};
+
+const REPORT_DIR: &str = "crash_reports";
+const REPORT_PREFIX: &str = "crash-";
+/// Reports that haven't been announced on startup yet.
+const NEW_EXTENSION: &str = ".new.txt";
+const EXTENSION: &str = ".txt";
+
+fn report_dir(profile: &Profile) -> Option<PathBuf> {
+    profile.state_dir().map(|dir| dir.join(REPORT_DIR))
+}
+
+/// Writes a crash report into the profile's state directory whenever a thread panics. Reports
+/// are only stored locally, nothing is sent anywhere.
+pub fn install_panic_hook(profile: &Profile) {
+    let dir = match report_dir(profile) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // nothing here goes through the logger, the panicking thread may hold its locks
+        let written = match write_report(&dir, info) {
+            Ok(path) => writeln!(
+                io::stderr(),
+                "Crashed, report written to {}",
+                path.display()
+            ),
+            Err(err) => writeln!(io::stderr(), "Crashed, cannot write report: {}", err),
+        };
+        // there's no stderr in the Windows release build
+        written.ok();
+        default_hook(info);
+    }));
+}
+
+fn write_report(dir: &Path, info: &PanicInfo) -> io::Result<PathBuf> {
+    let now = chrono::Local::now();
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => String::from(*message),
+        None => match info.payload().downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => String::from("Box<Any>"),
+        },
+    };
+    let location = info
+        .location()
+        .map(|location| format!("{}:{}", location.file(), location.line()))
+        .unwrap_or_default();
+
+    let mut report = String::new();
+    writeln!(report, "openbook-desktop crash report").ok();
+    writeln!(report, "Time: {}", now.to_rfc3339()).ok();
+    writeln!(report, "Version: {}", build_info::VERSION).ok();
+    writeln!(report, "Commit: {}", build_info::GIT_COMMIT).ok();
+    writeln!(report, "openbook-app: {}", build_info::APP_REVISION).ok();
+    writeln!(report, "Flutter engine: {}", build_info::ENGINE_VERSION).ok();
+    writeln!(report, "OS: {}", os_info()).ok();
+    writeln!(
+        report,
+        "Thread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    )
+    .ok();
+    writeln!(report, "Panic: {} at {}", message, location).ok();
+    writeln!(report, "\nBacktrace:\n{:?}", Backtrace::new()).ok();
+    writeln!(report, "\nLast log lines:").ok();
+    // only what's there without waiting, a panic while logging holds the buffer's lock
+    for line in logging::recent_lines() {
+        writeln!(report, "{}", line).ok();
+    }
+
+    fs::create_dir_all(dir)?;
+    // several threads can panic at once, or the app can crash again right after a restart
+    let path = dir.join(format!(
+        "{}{}-{}{}",
+        REPORT_PREFIX,
+        now.format("%Y%m%d-%H%M%S%.3f"),
+        process::id(),
+        NEW_EXTENSION
+    ));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?
+        .write_all(report.as_bytes())?;
+    Ok(path)
+}
+
+#[cfg(target_os = "linux")]
+fn os_info() -> String {
+    let name = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find(|line| line.starts_with("PRETTY_NAME="))
+                .map(|line| String::from(line["PRETTY_NAME=".len()..].trim_matches('"')))
+        })
+        .unwrap_or_else(|| String::from("Linux"));
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+    format!(
+        "{} (kernel {}, {})",
+        name,
+        kernel.trim(),
+        std::env::consts::ARCH
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn os_info() -> String {
+    format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn reports(dir: &Path) -> Vec<PathBuf> {
+    let mut reports = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .map_or(false, |name| {
+                        name.starts_with(REPORT_PREFIX) && name.ends_with(EXTENSION)
+                    })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    reports.sort();
+    reports
+}
+
+/// Tells the user about reports from crashes since the last start and offers to open the
+/// newest one. Each report is only announced once.
+pub fn announce_new_reports(profile: &Profile) {
+    let dir = match report_dir(profile) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let mut newest = None;
+    for report in reports(&dir) {
+        let name = report.file_name().unwrap().to_string_lossy().into_owned();
+        if !name.ends_with(NEW_EXTENSION) {
+            continue;
+        }
+        let seen = dir.join(name.replace(NEW_EXTENSION, EXTENSION));
+        warn!(
+            "Openbook crashed during a previous run, run with --crash-report to show the report \
+             at {}",
+            seen.display()
+        );
+        match fs::rename(&report, &seen) {
+            Ok(()) => newest = Some(seen),
+            Err(err) => warn!("Cannot mark crash report as seen: {}", err),
+        }
+    }
+
+    if let Some(report) = newest {
+        notification::offer(
+            "Openbook crashed last time",
+            &format!(
+                "A crash report was saved to {}. It hasn't been sent anywhere.",
+                report.display()
+            ),
+            "Show report",
+            move || open_report(&report),
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+const OPENER: &str = "xdg-open";
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(windows)]
+const OPENER: &str = "notepad";
+
+fn open_report(report: &Path) {
+    match Command::new(OPENER).arg(report).status() {
+        Ok(status) if status.success() => (),
+        Ok(status) => warn!(
+            "Cannot open crash report, {} exited with {}",
+            OPENER, status
+        ),
+        Err(err) => warn!("Cannot open crash report with {}: {}", OPENER, err),
+    }
+}
+
+/// Prints the newest crash report, returns false if there is none.
+pub fn print_latest_report(profile: &Profile) -> bool {
+    let report = match report_dir(profile).and_then(|dir| reports(&dir).pop()) {
+        Some(report) => report,
+        None => return false,
+    };
+    match fs::read_to_string(&report) {
+        Ok(content) => {
+            println!("{}\n", report.display());
+            println!("{}", content);
+            true
+        }
+        Err(_) => false,
+    }
+}
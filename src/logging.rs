@@ -1,48 +1,141 @@
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
 use fern::colors::{Color, ColoredLevelConfig};
 
-pub fn setup_logging(verbosity: u8, log_to_file: bool) -> Result<(), fern::InitError> {
+pub use self::{json::PluginCallScope, recent::recent_lines, terminal::ColorMode};
+
+use self::{filter::LogFilter, recent::RecentLines, rotating_file::RotatingFile};
+use crate::{
+    config::{LogFormat, LoggingConfig},
+    options::Options,
+    profile::Profile,
+};
+
+mod filter;
+mod json;
+mod recent;
+mod rotating_file;
+mod terminal;
+
+const LOG_FILE_NAME: &str = "openbook-desktop.log";
+const LOG_FILTER_ENV: &str = "RUST_LOG";
+
+pub fn log_file_path(profile: &Profile) -> Option<PathBuf> {
+    profile.state_dir().map(|dir| dir.join(LOG_FILE_NAME))
+}
+
+pub struct LogFilters {
+    stdout: LogFilter,
+    file: LogFilter,
+}
+
+impl LogFilters {
+    /// Picks the filter for each sink. Sink specific options win over general ones, the command
+    /// line wins over `RUST_LOG`, which wins over the config.
+    pub fn resolve(options: &Options, config: &LoggingConfig) -> Self {
+        let env = env::var(LOG_FILTER_ENV).ok();
+        let resolve = |cli: &Option<String>, config_spec: &Option<String>| {
+            let spec = cli
+                .as_ref()
+                .or_else(|| options.log.as_ref())
+                .or_else(|| env.as_ref())
+                .or_else(|| config_spec.as_ref())
+                .or_else(|| config.filter.as_ref());
+            match spec.map(|spec| LogFilter::parse(spec)) {
+                Some(Ok(filter)) => filter,
+                Some(Err(err)) => {
+                    eprintln!("Ignoring log filter: {}", err);
+                    LogFilter::default()
+                }
+                None => LogFilter::default(),
+            }
+        };
+        Self {
+            stdout: resolve(&options.log_stdout, &config.stdout_filter),
+            file: resolve(&options.log_file, &config.file_filter),
+        }
+    }
+}
+
+fn format_text(out: fern::FormatCallback, message: &std::fmt::Arguments, record: &log::Record) {
+    out.finish(format_args!(
+        "[{}][{}][{}] {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        record.level(),
+        record.target(),
+        message
+    ))
+}
+
+pub fn setup_logging(
+    verbosity: u8,
+    filters: &LogFilters,
+    color: ColorMode,
+    log_file: Option<&Path>,
+    config: &LoggingConfig,
+) -> Result<(), fern::InitError> {
     let colors = ColoredLevelConfig::new()
         .error(Color::Red)
         .warn(Color::Yellow)
         .info(Color::Green)
         .debug(Color::White)
         .trace(Color::BrightBlack);
-    let mut logger = fern::Dispatch::new();
-    logger = match verbosity {
-        0 => logger.level(log::LevelFilter::Warn),
-        1 => logger.level(log::LevelFilter::Info),
-        2 => logger.level(log::LevelFilter::Debug),
-        _ => logger.level(log::LevelFilter::Trace),
+    let level = match verbosity {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
     };
+    let mut logger = fern::Dispatch::new();
+
+    if terminal::has_stdout() {
+        let stdout_logger = filters.stdout.apply(fern::Dispatch::new(), level);
+        let stdout_logger = match (config.stdout_format, color.use_color()) {
+            (LogFormat::Text, true) => stdout_logger.format(move |out, message, record| {
+                out.finish(format_args!(
+                    "{}[{}][{}][{}] {}\x1B[0m",
+                    format_args!("\x1B[{}m", colors.get_color(&record.level()).to_fg_str()),
+                    chrono::Local::now().format("%H:%M:%S"),
+                    record.level(),
+                    record.target(),
+                    message
+                ))
+            }),
+            (LogFormat::Text, false) => stdout_logger.format(|out, message, record| {
+                out.finish(format_args!(
+                    "[{}][{}][{}] {}",
+                    chrono::Local::now().format("%H:%M:%S"),
+                    record.level(),
+                    record.target(),
+                    message
+                ))
+            }),
+            (LogFormat::Json, _) => stdout_logger.format(json::format),
+        };
+        logger = logger.chain(stdout_logger.chain(std::io::stdout()));
+    }
 
-    let stdout_logger = fern::Dispatch::new()
-        .format(move |out, message, record| {
-            out.finish(format_args!(
-                "{}[{}][{}][{}] {}\x1B[0m",
-                format_args!("\x1B[{}m", colors.get_color(&record.level()).to_fg_str()),
-                chrono::Local::now().format("%H:%M:%S"),
-                record.level(),
-                record.target(),
-                message
-            ))
-        })
-        .chain(std::io::stdout());
-
-    if log_to_file {
-        logger = logger.chain(
-            fern::Dispatch::new()
-                .format(|out, message, record| {
-                    out.finish(format_args!(
-                        "[{}][{}][{}] {}",
-                        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                        record.level(),
-                        record.target(),
-                        message
-                    ))
-                })
-                .chain(fern::log_file("openbook-desktop.log")?),
-        );
+    if let Some(path) = log_file {
+        let file: Box<dyn Write + Send> = Box::new(RotatingFile::open(path, config)?);
+        let file_logger = filters.file.apply(fern::Dispatch::new(), level);
+        let file_logger = match config.file_format {
+            LogFormat::Text => file_logger.format(format_text),
+            LogFormat::Json => file_logger.format(json::format),
+        };
+        logger = logger.chain(file_logger.chain(file));
     }
-    logger.chain(stdout_logger).apply()?;
+
+    // the last lines for crash reports use the same filter as the log file
+    let recent: Box<dyn Write + Send> = Box::new(RecentLines::default());
+    logger = logger.chain(
+        filters
+            .file
+            .apply(fern::Dispatch::new(), level)
+            .format(format_text)
+            .chain(recent),
+    );
+    logger.apply()?;
     Ok(())
 }
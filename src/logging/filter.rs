@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+/// Per-target log levels in the format of `RUST_LOG`, e.g.
+/// `info,openbook_desktop::plugins::path_provider=trace,flutter_engine=warn`.
+///
+/// A directive without `=` is either the default level or a target that gets logged at all
+/// levels.
+#[derive(Debug, Default, PartialEq)]
+pub struct LogFilter {
+    level: Option<LevelFilter>,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let target = parts.next().unwrap_or_default().trim();
+            match parts.next() {
+                Some(level) => {
+                    let level = LevelFilter::from_str(level.trim())
+                        .map_err(|_| format!("Invalid log level in directive {}", directive))?;
+                    if target.is_empty() {
+                        return Err(format!("Missing target in directive {}", directive));
+                    }
+                    filter.targets.push((String::from(target), level));
+                }
+                None => match LevelFilter::from_str(target) {
+                    Ok(level) => filter.level = Some(level),
+                    Err(_) => filter
+                        .targets
+                        .push((String::from(target), LevelFilter::Trace)),
+                },
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Applies the filter to a sink, using `default` for all targets without a directive.
+    pub fn apply(&self, dispatch: fern::Dispatch, default: LevelFilter) -> fern::Dispatch {
+        let mut dispatch = dispatch.level(self.level.unwrap_or(default));
+        for (target, level) in self.targets.iter() {
+            dispatch = dispatch.level_for(target.clone(), *level);
+        }
+        dispatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogFilter;
+
+    use log::LevelFilter;
+
+    #[test]
+    fn test_parse() {
+        let filter = LogFilter::parse(
+            "info, openbook_desktop::plugins::path_provider=trace,flutter_engine=WARN,dart",
+        )
+        .expect("Cannot parse filter");
+        assert_eq!(
+            filter,
+            LogFilter {
+                level: Some(LevelFilter::Info),
+                targets: vec![
+                    (
+                        String::from("openbook_desktop::plugins::path_provider"),
+                        LevelFilter::Trace
+                    ),
+                    (String::from("flutter_engine"), LevelFilter::Warn),
+                    (String::from("dart"), LevelFilter::Trace),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(LogFilter::parse("flutter_engine=loud").is_err());
+        assert!(LogFilter::parse("=debug").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::thread;
+
+use serde_json::json;
+
+thread_local! {
+    static PLUGIN_CALL: RefCell<Option<(String, String)>> = RefCell::new(None);
+}
+
+/// Adds the channel and method of a plugin call to all records logged on this thread until the
+/// scope is dropped.
+pub struct PluginCallScope {
+    previous: Option<(String, String)>,
+}
+
+impl PluginCallScope {
+    pub fn enter(channel: &str, method: &str) -> Self {
+        let call = Some((String::from(channel), String::from(method)));
+        Self {
+            previous: PLUGIN_CALL.with(|current| current.replace(call)),
+        }
+    }
+}
+
+impl Drop for PluginCallScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        PLUGIN_CALL.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Formats a record as a single line JSON object.
+pub fn format(out: fern::FormatCallback, message: &fmt::Arguments, record: &log::Record) {
+    let thread = thread::current();
+    let mut object = json!({
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": message.to_string(),
+        "thread": thread
+            .name()
+            .map(String::from)
+            .unwrap_or_else(|| format!("{:?}", thread.id())),
+    });
+    PLUGIN_CALL.with(|call| {
+        if let Some((channel, method)) = &*call.borrow() {
+            object["channel"] = json!(channel);
+            object["method"] = json!(method);
+        }
+    });
+    out.finish(format_args!("{}", object))
+}
@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Mutex, TryLockError};
+
+use lazy_static::lazy_static;
+
+const MAX_LINES: usize = 200;
+
+lazy_static! {
+    static ref RECENT_LINES: Mutex<VecDeque<String>> =
+        Mutex::new(VecDeque::with_capacity(MAX_LINES));
+}
+
+/// Returns the most recently logged lines, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    // this runs in the panic hook, which must not wait for a lock held by the panicking thread
+    match RECENT_LINES.try_lock() {
+        Ok(lines) => lines.iter().cloned().collect(),
+        // the lines are still fine if another thread panicked while logging
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().iter().cloned().collect(),
+        Err(TryLockError::WouldBlock) => Vec::new(),
+    }
+}
+
+/// Log sink keeping the last lines in memory so they can be added to crash reports.
+#[derive(Default)]
+pub struct RecentLines {
+    line: Vec<u8>,
+}
+
+impl Write for RecentLines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            if *byte != b'\n' {
+                self.line.push(*byte);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+            if let Ok(mut lines) = RECENT_LINES.lock() {
+                if lines.len() >= MAX_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Date, DateTime, Local};
+use flate2::{write::GzEncoder, Compression};
+
+use crate::config::LoggingConfig;
+
+const ARCHIVE_EXTENSION: &str = ".log.gz";
+
+/// A log file that is rotated once it grows too large or a new day starts. Rotated files are
+/// compressed next to the log file and only the newest archives are kept.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    date: Date<Local>,
+    max_size: u64,
+    max_archives: usize,
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, config: &LoggingConfig) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = Self {
+            path: path.to_path_buf(),
+            file: Self::open_file(path)?,
+            size: 0,
+            date: Local::today(),
+            max_size: config.max_file_size,
+            max_archives: config.max_archives,
+            at_line_start: true,
+        };
+        let metadata = file.file.metadata()?;
+        file.size = metadata.len();
+        if let Ok(modified) = metadata.modified() {
+            file.date = DateTime::<Local>::from(modified).date();
+        }
+        if file.size > 0 && (file.size >= file.max_size || file.date != Local::today()) {
+            file.rotate()?;
+        }
+        Ok(file)
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn base_name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn archive_prefix(&self) -> String {
+        format!("{}-", self.base_name())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let stamp = self.date.format("%Y%m%d");
+        let mut archive = dir.join(format!(
+            "{}{}{}",
+            self.archive_prefix(),
+            stamp,
+            ARCHIVE_EXTENSION
+        ));
+        let mut counter = 1;
+        while archive.exists() {
+            archive = dir.join(format!(
+                "{}{}.{}{}",
+                self.archive_prefix(),
+                stamp,
+                counter,
+                ARCHIVE_EXTENSION
+            ));
+            counter += 1;
+        }
+
+        let mut encoder = GzEncoder::new(File::create(&archive)?, Compression::default());
+        io::copy(&mut BufReader::new(File::open(&self.path)?), &mut encoder)?;
+        encoder.finish()?;
+
+        self.file.set_len(0)?;
+        self.size = 0;
+        self.date = Local::today();
+        self.remove_old_archives(dir)
+    }
+
+    fn remove_old_archives(&self, dir: &Path) -> io::Result<()> {
+        let prefix = self.archive_prefix();
+        let mut archives = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with(&prefix) && name.ends_with(ARCHIVE_EXTENSION)
+            })
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect::<Vec<_>>();
+        if archives.len() <= self.max_archives {
+            return Ok(());
+        }
+        archives.sort();
+        let count = archives.len() - self.max_archives;
+        for (_, path) in archives.into_iter().take(count) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // only rotate between lines so records never get split across files
+        if self.at_line_start
+            && self.size > 0
+            && (self.size + buf.len() as u64 > self.max_size || self.date != Local::today())
+        {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::RotatingFile;
+    use crate::config::LoggingConfig;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_rotation() {
+        let dir = TempDir::new("logs");
+        let config = LoggingConfig {
+            max_file_size: 16,
+            max_archives: 2,
+            ..Default::default()
+        };
+        let mut file = RotatingFile::open(&dir.join("test.log"), &config).expect("Cannot open log");
+        for line in 0..5 {
+            writeln!(file, "log line {:05}", line).expect("Cannot write log");
+        }
+
+        let mut names = fs::read_dir(&dir)
+            .expect("Cannot read log dir")
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names.len(), 3, "Unexpected log files {:?}", names);
+        assert!(
+            names
+                .iter()
+                .filter(|name| name.ends_with(".log.gz"))
+                .count()
+                == 2
+        );
+        assert!(names.contains(&String::from("test.log")));
+    }
+}
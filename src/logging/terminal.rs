@@ -0,0 +1,59 @@
+use std::env;
+use std::str::FromStr;
+
+const NO_COLOR_ENV: &str = "NO_COLOR";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Color only when stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+}
+
+impl Default for ColorMode {
+    fn default() -> Self {
+        ColorMode::Auto
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            "auto" => Ok(ColorMode::Auto),
+            _ => Err(format!("Invalid color mode {}", s)),
+        }
+    }
+}
+
+impl ColorMode {
+    pub fn use_color(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                env::var_os(NO_COLOR_ENV).map_or(true, |value| value.is_empty())
+                    && atty::is(atty::Stream::Stdout)
+            }
+        }
+    }
+}
+
+/// Checks whether stdout goes anywhere. Builds for the Windows GUI subsystem don't get a console,
+/// so writing log records to stdout would only waste time.
+#[cfg(windows)]
+pub fn has_stdout() -> bool {
+    use winapi::um::{handleapi::INVALID_HANDLE_VALUE, processenv::GetStdHandle, winbase};
+
+    let handle = unsafe { GetStdHandle(winbase::STD_OUTPUT_HANDLE) };
+    !handle.is_null() && handle != INVALID_HANDLE_VALUE
+}
+
+#[cfg(not(windows))]
+pub fn has_stdout() -> bool {
+    true
+}
@@ -1,12 +1,32 @@
 #![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
 
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use flutter_engine::{FlutterEngine, FlutterEngineArgs};
-use log::{debug, info};
+use log::{debug, info, warn};
 
+mod build_info;
+mod config;
+mod crash_report;
 mod logging;
+mod notification;
+mod options;
 mod plugins;
+#[cfg(target_os = "linux")]
+mod portal;
+mod profile;
+mod sentry;
+#[cfg(test)]
+mod testing;
+mod window_state;
+
+use self::{config::Config, options::Options, profile::Profile, window_state::WindowState};
+
+const SENTRY_DIR: &str = "sentry_events";
 
 fn get_res_dir() -> PathBuf {
     env::current_exe()
@@ -17,12 +37,52 @@ fn get_res_dir() -> PathBuf {
 }
 
 fn main() {
-    if cfg!(debug_assertions) {
-        logging::setup_logging(2, false).expect("Failed to setup logging");
+    let options = Options::from_env();
+    let profile = Profile::new(options.profile.clone());
+    let config = Config::load(&profile);
+    let log_file = logging::log_file_path(&profile);
+    crash_report::install_panic_hook(&profile);
+
+    if options.version {
+        println!("openbook-desktop {}", build_info::VERSION);
+        println!("Commit: {}", build_info::GIT_COMMIT);
+        println!("openbook-app: {}", build_info::APP_REVISION);
+        println!("Flutter engine: {}", build_info::ENGINE_VERSION);
+        println!("Profile: {}", profile.name());
+        if let Some(log_file) = &log_file {
+            println!("Log file: {}", log_file.display());
+        }
+        return;
+    }
+    if options.crash_report {
+        if !crash_report::print_latest_report(&profile) {
+            println!("No crash reports found");
+        }
+        return;
+    }
+
+    let log_filters = logging::LogFilters::resolve(&options, &config.logging);
+    let log_file = if cfg!(debug_assertions) {
+        logging::setup_logging(2, &log_filters, options.color, None, &config.logging)
+            .expect("Failed to setup logging");
+        None
     } else {
-        logging::setup_logging(1, true).expect("Failed to setup logging");
+        logging::setup_logging(
+            1,
+            &log_filters,
+            options.color,
+            log_file.as_ref().map(PathBuf::as_path),
+            &config.logging,
+        )
+        .expect("Failed to setup logging");
+        log_file
+    };
+    info!("Starting {}", build_info::summary());
+    info!("Using profile {}", profile.name());
+    if let Some(log_file) = &log_file {
+        info!("Logging to {}", log_file.display());
     }
-    info!("Starting openbook-desktop");
+    crash_report::announce_new_reports(&profile);
     debug!("Loading flutter engine");
     flutter_engine::init();
 
@@ -44,22 +104,72 @@ fn main() {
         }
     };
 
+    let sentry = match (config.sentry.enabled, profile.state_dir()) {
+        (true, Some(dir)) => {
+            match sentry::LocalSentry::start(dir.join(SENTRY_DIR), config.sentry.port) {
+                Ok(sentry) => Some(sentry),
+                Err(err) => {
+                    warn!("Cannot start local Sentry endpoint: {}", err);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let window_state = Arc::new(Mutex::new(WindowState::load(&profile)));
+    let last_window = plugins::LastWindow::default();
+    let (width, height) = {
+        let state = window_state.lock().unwrap();
+        (state.width, state.height)
+    };
+
     let args = FlutterEngineArgs {
         assets_path: assets_path.to_string_lossy().into_owned(),
         icu_data_path: icu_data_path.to_string_lossy().into_owned(),
         title: String::from("Openbook"),
-        width: 800,
-        height: 600,
+        width,
+        height,
         ..Default::default()
     };
 
     debug!("Creating flutter engine");
     let engine = FlutterEngine::new(args);
     info!("Registering plugins");
-    engine.add_plugin(Box::new(plugins::FlutterSecureStoragePlugin::new()));
-    engine.add_plugin(Box::new(plugins::PathProviderPlugin::new()));
+    engine.add_plugin(Box::new(plugins::LoggingPlugin::new()));
+    engine.add_plugin(Box::new(plugins::BuildInfoPlugin::new()));
+    engine.add_plugin(Box::new(plugins::ConnectivityPlugin::new()));
+    engine.add_plugin(Box::new(plugins::ConnectivityStatusPlugin::new()));
+    engine.add_plugin(Box::new(plugins::PackageInfoPlugin::new(&assets_path)));
+    engine.add_plugin(Box::new(plugins::DeviceInfoPlugin::new()));
+    engine.add_plugin(Box::new(plugins::ExifRotationPlugin::new(&config.images)));
+    engine.add_plugin(Box::new(plugins::FlutterSecureStoragePlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::ImageCompressPlugin::new()));
+    engine.add_plugin(Box::new(plugins::ImageCropperPlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::ImagePickerPlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::PathProviderPlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::SharedPreferencesPlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::SharePlugin::new(&config.share)));
+    engine.add_plugin(Box::new(plugins::SqflitePlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::UrlLauncherPlugin::new(
+        &config.url_launcher,
+    )));
+    engine.add_plugin(Box::new(plugins::VideoThumbnailPlugin::new(&profile)));
+    engine.add_plugin(Box::new(plugins::WindowPlugin::new(
+        window_state.clone(),
+        last_window.clone(),
+    )));
+    engine.add_plugin(Box::new(plugins::SentryPlugin::new(
+        sentry.as_ref().map(sentry::LocalSentry::dsn),
+    )));
     debug!("Running app");
     engine.run();
     info!("Shutting down");
+    {
+        // the window is closed but still exists until the engine shuts down
+        let mut state = window_state.lock().unwrap();
+        last_window.capture(&mut state);
+        state.save(&profile);
+    }
     engine.shutdown();
 }
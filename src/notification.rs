@@ -0,0 +1,176 @@
+use std::thread;
+
+use log::info;
+
+const APP_NAME: &str = "Openbook";
+
+/// Shows a desktop notification. Where there's no notification service, it's only logged.
+pub fn show(summary: &str, body: &str) {
+    if let Err(err) = show_notification(summary, body) {
+        info!("Cannot show notification: {}", err);
+        info!("{}: {}", summary, body);
+    }
+}
+
+/// Shows a notification with a button. `on_action` runs on a background thread if the user
+/// clicks it, nothing happens if the notification is dismissed.
+pub fn offer<F>(summary: &str, body: &str, label: &str, on_action: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let (summary, body, label) = (
+        String::from(summary),
+        String::from(body),
+        String::from(label),
+    );
+    let spawned = thread::Builder::new()
+        .name(String::from("notification"))
+        .spawn(move || match wait_for_action(&summary, &body, &label) {
+            Ok(true) => on_action(),
+            Ok(false) => (),
+            Err(err) => {
+                info!("Cannot show notification: {}", err);
+                info!("{}: {}", summary, body);
+            }
+        });
+    if let Err(err) = spawned {
+        info!("Cannot show notification: {}", err);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use dbus::{
+        arg::PropMap,
+        blocking::{Connection, Proxy},
+        message::MatchRule,
+    };
+
+    use super::APP_NAME;
+
+    const DESTINATION: &str = "org.freedesktop.Notifications";
+    const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+    const CALL_TIMEOUT: Duration = Duration::from_secs(1);
+    /// How long a button stays clickable, the notification server may keep it around longer.
+    const ACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+    const ACTION_KEY: &str = "default";
+
+    fn proxy(connection: &Connection) -> Proxy<'_, &Connection> {
+        connection.with_proxy(DESTINATION, OBJECT_PATH, CALL_TIMEOUT)
+    }
+
+    fn notify(
+        connection: &Connection,
+        summary: &str,
+        body: &str,
+        actions: Vec<&str>,
+    ) -> Result<u32, dbus::Error> {
+        let (id,) = proxy(connection).method_call(
+            DESTINATION,
+            "Notify",
+            (
+                APP_NAME,
+                0u32,
+                "",
+                summary,
+                body,
+                actions,
+                PropMap::new(),
+                // the server's default timeout
+                -1i32,
+            ),
+        )?;
+        Ok(id)
+    }
+
+    pub fn show(summary: &str, body: &str) -> Result<(), dbus::Error> {
+        notify(&Connection::new_session()?, summary, body, Vec::new()).map(|_| ())
+    }
+
+    /// Returns whether the button was clicked.
+    pub fn wait_for_action(summary: &str, body: &str, label: &str) -> Result<bool, dbus::Error> {
+        let connection = Connection::new_session()?;
+        // (id, clicked) of notifications that are done, matched once our id is known
+        let done = Arc::new(Mutex::new(Vec::new()));
+
+        let invoked = done.clone();
+        connection.add_match(
+            MatchRule::new_signal(DESTINATION, "ActionInvoked"),
+            move |(id, _action): (u32, String), _, _| {
+                invoked.lock().unwrap().push((id, true));
+                true
+            },
+        )?;
+        let closed = done.clone();
+        connection.add_match(
+            MatchRule::new_signal(DESTINATION, "NotificationClosed"),
+            move |(id, _reason): (u32, u32), _, _| {
+                closed.lock().unwrap().push((id, false));
+                true
+            },
+        )?;
+
+        let id = notify(&connection, summary, body, vec![ACTION_KEY, label])?;
+        let deadline = Instant::now() + ACTION_TIMEOUT;
+        while Instant::now() < deadline {
+            connection.process(Duration::from_millis(500))?;
+            // a click is usually followed by a close, the first one decides
+            if let Some((_, clicked)) = done.lock().unwrap().iter().find(|(done, _)| *done == id) {
+                return Ok(*clicked);
+            }
+        }
+        proxy(&connection).method_call::<(), _, _, _>(DESTINATION, "CloseNotification", (id,))?;
+        Ok(false)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn show_notification(summary: &str, body: &str) -> Result<(), String> {
+    linux::show(summary, body).map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn wait_for_action(summary: &str, body: &str, label: &str) -> Result<bool, String> {
+    linux::wait_for_action(summary, body, label).map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn show_notification(_summary: &str, _body: &str) -> Result<(), String> {
+    Err(String::from("not supported on this system"))
+}
+
+/// Asks with a message box, since there are no notification buttons without extra libraries.
+#[cfg(windows)]
+fn wait_for_action(summary: &str, body: &str, label: &str) -> Result<bool, String> {
+    use std::ffi::OsStr;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use winapi::um::winuser::{MessageBoxW, IDYES, MB_ICONWARNING, MB_YESNO};
+
+    let wide = |text: &str| {
+        OsStr::new(text)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect::<Vec<u16>>()
+    };
+    let text = wide(&format!("{}\n\n{}?", body, label));
+    let caption = wide(&format!("{}: {}", APP_NAME, summary));
+    let answer = unsafe {
+        MessageBoxW(
+            ptr::null_mut(),
+            text.as_ptr(),
+            caption.as_ptr(),
+            MB_YESNO | MB_ICONWARNING,
+        )
+    };
+    Ok(answer == IDYES)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn wait_for_action(_summary: &str, _body: &str, _label: &str) -> Result<bool, String> {
+    Err(String::from("not supported on this system"))
+}
@@ -0,0 +1,59 @@
+use std::env;
+
+use crate::logging::ColorMode;
+
+const PROFILE_ENV: &str = "OPENBOOK_PROFILE";
+
+pub struct Options {
+    pub profile: Option<String>,
+    pub version: bool,
+    /// Print the newest crash report and exit.
+    pub crash_report: bool,
+    /// Log filter for all sinks, overrides `RUST_LOG` and the config.
+    pub log: Option<String>,
+    pub log_stdout: Option<String>,
+    pub log_file: Option<String>,
+    pub color: ColorMode,
+}
+
+impl Options {
+    pub fn from_env() -> Self {
+        let mut options = Self {
+            profile: env::var(PROFILE_ENV).ok(),
+            version: false,
+            crash_report: false,
+            log: None,
+            log_stdout: None,
+            log_file: None,
+            color: ColorMode::default(),
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            // options with values can be given as `--name value` or `--name=value`
+            let (name, inline_value) = match arg.find('=') {
+                Some(index) if arg.starts_with("--") => {
+                    (&arg[..index], Some(String::from(&arg[index + 1..])))
+                }
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next());
+            match name {
+                "--profile" => options.profile = value(),
+                "--version" | "-V" => options.version = true,
+                "--crash-report" => options.crash_report = true,
+                "--log" => options.log = value(),
+                "--log-stdout" => options.log_stdout = value(),
+                "--log-file" => options.log_file = value(),
+                "--color" => match value().map(|value| value.parse()) {
+                    Some(Ok(color)) => options.color = color,
+                    Some(Err(err)) => eprintln!("{}", err),
+                    None => eprintln!("Missing value for --color"),
+                },
+                _ => eprintln!("Ignoring unknown argument {}", arg),
+            }
+        }
+
+        options
+    }
+}
@@ -1,5 +1,22 @@
 pub use self::{
-    flutter_secure_storage::FlutterSecureStoragePlugin, path_provider::PathProviderPlugin,
+    build_info::BuildInfoPlugin,
+    connectivity::{ConnectivityPlugin, ConnectivityStatusPlugin},
+    device_info::DeviceInfoPlugin,
+    exif_rotation::ExifRotationPlugin,
+    flutter_secure_storage::FlutterSecureStoragePlugin,
+    image_compress::ImageCompressPlugin,
+    image_cropper::ImageCropperPlugin,
+    image_picker::ImagePickerPlugin,
+    logging::LoggingPlugin,
+    package_info::PackageInfoPlugin,
+    path_provider::PathProviderPlugin,
+    sentry::SentryPlugin,
+    share::SharePlugin,
+    shared_preferences::SharedPreferencesPlugin,
+    sqflite::SqflitePlugin,
+    url_launcher::UrlLauncherPlugin,
+    video_thumbnail::VideoThumbnailPlugin,
+    window::{LastWindow, WindowPlugin},
 };
 
 use flutter_engine::codec::standard_codec::Value;
@@ -57,8 +74,27 @@ macro_rules! plugin_args {
     };
 }
 
+mod build_info;
+mod connectivity;
+mod device_info;
+mod exif_rotation;
 mod flutter_secure_storage;
+mod image_compress;
+mod image_cropper;
+mod image_picker;
+mod images;
+mod json_store;
+mod logging;
+mod package_info;
 mod path_provider;
+mod sentry;
+mod share;
+mod shared_preferences;
+mod sqflite;
+mod url_launcher;
+mod video_thumbnail;
+mod window;
+mod worker;
 
 enum DecodeError {
     WrongType,
@@ -66,6 +102,18 @@ enum DecodeError {
     MissingMapKey,
 }
 
+/// Returns an optional argument from a map of arguments, treating `null` like a missing key.
+/// For methods whose arguments vary between plugin versions, where `plugin_args!` is too strict.
+fn map_value<'a>(args: &'a Value, key: &str) -> Option<&'a Value> {
+    match args {
+        Value::Map(map) => match map.get(&Value::String(String::from(key))) {
+            Some(Value::Null) | None => None,
+            value => value,
+        },
+        _ => None,
+    }
+}
+
 fn debug_print_args(value: &Value) -> String {
     match value {
         Value::String(string) => format!("String: {}", string),
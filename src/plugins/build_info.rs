@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{build_info, logging::PluginCallScope};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::debug;
+
+const CHANNEL_NAME: &str = "openbook_desktop/build_info";
+
+/// Returns the versions this binary was built with, so bug reports from the app can include
+/// them.
+pub struct BuildInfoPlugin {
+    channel: StandardMethodChannel,
+}
+
+impl BuildInfoPlugin {
+    pub fn new() -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+        }
+    }
+
+    fn get_all(&self) -> MethodCallResult<Value> {
+        let mut map = HashMap::<Value, Value>::new();
+        for (key, value) in &[
+            ("version", build_info::VERSION),
+            ("gitCommit", build_info::GIT_COMMIT),
+            ("appRevision", build_info::APP_REVISION),
+            ("engineVersion", build_info::ENGINE_VERSION),
+        ] {
+            map.insert(
+                Value::String(String::from(*key)),
+                Value::String(String::from(*value)),
+            );
+        }
+        MethodCallResult::Ok(Value::Map(map))
+    }
+}
+
+impl Plugin for BuildInfoPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "getAll" => {
+                let response = self.get_all();
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            _ => (),
+        }
+    }
+}
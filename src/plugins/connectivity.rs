@@ -0,0 +1,222 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::worker::Worker;
+use crate::logging::PluginCallScope;
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, info};
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/connectivity";
+const STATUS_CHANNEL_NAME: &str = "plugins.flutter.io/connectivity_status";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connectivity {
+    Wifi,
+    Mobile,
+    None,
+}
+
+impl Connectivity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Connectivity::Wifi => "wifi",
+            Connectivity::Mobile => "mobile",
+            Connectivity::None => "none",
+        }
+    }
+}
+
+/// Asks NetworkManager first since it knows about the primary connection, and looks at the
+/// interfaces with a default route if it isn't running.
+#[cfg(target_os = "linux")]
+fn current() -> Connectivity {
+    match linux::network_manager() {
+        Ok(Some(connectivity)) => connectivity,
+        Ok(None) => linux::detect(std::path::Path::new("/")),
+        Err(err) => {
+            debug!("NetworkManager not available: {}", err);
+            linux::detect(std::path::Path::new("/"))
+        }
+    }
+}
+
+/// There's no detection on other systems yet. Claiming to be online lets the app try and show
+/// its usual errors, instead of refusing to refresh at all.
+#[cfg(not(target_os = "linux"))]
+fn current() -> Connectivity {
+    Connectivity::Wifi
+}
+
+/// Tells the app whether it's on wifi, mobile data or offline. Wired connections count as wifi,
+/// like on Android. Checks run on a worker thread, since asking NetworkManager can take a while.
+pub struct ConnectivityPlugin {
+    channel: Arc<StandardMethodChannel>,
+    worker: Worker,
+}
+
+impl ConnectivityPlugin {
+    pub fn new() -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            worker: Worker::new("connectivity"),
+        }
+    }
+}
+
+impl Plugin for ConnectivityPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        let response = match decoded.method.as_str() {
+            "check" => {
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+                    let response = Value::String(String::from(current().as_str()));
+                    channel
+                        .send_method_call_response(response_handle, MethodCallResult::Ok(response));
+                });
+                return;
+            }
+            // the network details need location permissions on mobile, the app doesn't use them
+            "wifiName" | "wifiBSSID" | "wifiIPAddress" => Value::Null,
+            _ => return,
+        };
+        self.channel
+            .send_method_call_response(msg.response_handle, MethodCallResult::Ok(response));
+    }
+}
+
+/// The state of the `onConnectivityChanged` stream, shared with the thread watching for
+/// network changes.
+struct StatusEvents {
+    channel: Arc<StandardMethodChannel>,
+    listening: AtomicBool,
+    /// What the app was told last, `None` before the first check.
+    last: Mutex<Option<Connectivity>>,
+}
+
+impl StatusEvents {
+    /// Checks the connectivity and sends it to the app if it's listening, and if it changed or
+    /// `always` is set. Holding the lock while checking keeps the events in order.
+    fn refresh(&self, always: bool) {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        let connectivity = current();
+        if *last == Some(connectivity) && !always {
+            return;
+        }
+        if last.is_some() && *last != Some(connectivity) {
+            info!("Connectivity changed to {}", connectivity.as_str());
+        }
+        *last = Some(connectivity);
+        if self.listening.load(Ordering::SeqCst) {
+            self.channel
+                .send_success_event(&Value::String(String::from(connectivity.as_str())));
+        }
+    }
+}
+
+/// Handles the `onConnectivityChanged` stream. It starts with the current connectivity, like
+/// the sticky broadcast on Android, and changes are picked up from netlink.
+pub struct ConnectivityStatusPlugin {
+    events: Arc<StatusEvents>,
+    worker: Worker,
+    watching: bool,
+}
+
+impl ConnectivityStatusPlugin {
+    pub fn new() -> Self {
+        Self {
+            events: Arc::new(StatusEvents {
+                channel: Arc::new(StandardMethodChannel::new(STATUS_CHANNEL_NAME)),
+                listening: AtomicBool::new(false),
+                last: Mutex::new(None),
+            }),
+            worker: Worker::new("connectivity-status"),
+            watching: false,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch(&mut self) {
+        if self.watching {
+            return;
+        }
+        let events = self.events.clone();
+        match linux::watch(move || events.refresh(false)) {
+            Ok(()) => self.watching = true,
+            Err(err) => log::warn!("Cannot watch network changes: {}", err),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn watch(&mut self) {
+        if !self.watching {
+            info!("Watching network changes isn't supported on this system");
+            self.watching = true;
+        }
+    }
+}
+
+impl Plugin for ConnectivityStatusPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.events.channel.init(registry);
+        STATUS_CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.events.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(STATUS_CHANNEL_NAME, &decoded.method);
+
+        debug!("Got method call {}", decoded.method);
+        let listening = match decoded.method.as_str() {
+            "listen" => true,
+            "cancel" => false,
+            _ => return,
+        };
+        self.events.listening.store(listening, Ordering::SeqCst);
+        // events may only follow the response to `listen`
+        self.events
+            .channel
+            .send_method_call_response(msg.response_handle, MethodCallResult::Ok(Value::Null));
+        if listening {
+            let events = self.events.clone();
+            self.worker
+                .run(STATUS_CHANNEL_NAME, &decoded.method, move || {
+                    events.refresh(true)
+                });
+            // the watcher keeps running after cancel, it's cheap and the app listens again
+            // whenever a timeline is shown
+            self.watch();
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use super::Connectivity;
+
+use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
+use lazy_static::lazy_static;
+use log::warn;
+
+/// `RTF_UP` from `route.h`, set for usable routes.
+const RTF_UP: u32 = 0x0001;
+/// `ARPHRD_PPP` and `ARPHRD_RAWIP`, used by modems that don't pretend to be ethernet.
+const MOBILE_LINK_TYPES: &[&str] = &["512", "519"];
+
+const NM_DESTINATION: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+/// `NM_STATE_DISCONNECTED`, everything below is asleep or unknown.
+const NM_STATE_DISCONNECTED: u32 = 20;
+/// A change comes as a burst of link and route messages, it's checked once they stop.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+lazy_static! {
+    /// Kept open between checks, since connecting to the system bus is slower than asking.
+    static ref SYSTEM_BUS: Mutex<Option<Connection>> = Mutex::new(None);
+}
+
+/// Interfaces with a default route in `/proc/net/route`.
+fn parse_route(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+            let default = fields.get(1)? == &"00000000" && fields.get(7)? == &"00000000";
+            if default && flags & RTF_UP != 0 {
+                Some(String::from(fields[0]))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Interfaces with a default route in `/proc/net/ipv6_route`. The kernel always has an
+/// unreachable default route on `lo`, which is skipped.
+fn parse_ipv6_route(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let flags = u32::from_str_radix(fields.get(8)?, 16).ok()?;
+            let default = fields.first()?.chars().all(|c| c == '0') && fields.get(1)? == &"00";
+            let iface = *fields.get(9)?;
+            if default && flags & RTF_UP != 0 && iface != "lo" {
+                Some(String::from(iface))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// What kind of network an interface is connected to, or `None` if it's down.
+fn interface_kind(net: &Path, iface: &str) -> Option<Connectivity> {
+    let dir = net.join(iface);
+    let read = |name| {
+        fs::read_to_string(dir.join(name))
+            .map(|content| String::from(content.trim()))
+            .unwrap_or_default()
+    };
+    // modems and ppp links often don't report their state
+    match read("operstate").as_str() {
+        "up" | "unknown" => (),
+        _ => return None,
+    }
+    let uevent = read("uevent");
+    let devtype = uevent
+        .lines()
+        .find(|line| line.starts_with("DEVTYPE="))
+        .map(|line| &line["DEVTYPE=".len()..]);
+    Some(match devtype {
+        Some("wlan") => Connectivity::Wifi,
+        Some("wwan") => Connectivity::Mobile,
+        _ if dir.join("wireless").is_dir() => Connectivity::Wifi,
+        _ if MOBILE_LINK_TYPES.contains(&read("type").as_str()) => Connectivity::Mobile,
+        // the plugin has no ethernet, Android reports it as wifi too
+        _ => Connectivity::Wifi,
+    })
+}
+
+/// Detects the connectivity from the interfaces with a default route, below a filesystem root
+/// so tests can use a fake sysfs. Wifi wins if there are several, since mobile data is usually
+/// metered.
+pub fn detect(root: &Path) -> Connectivity {
+    let read = |path| fs::read_to_string(root.join(path)).unwrap_or_default();
+    let mut interfaces = parse_route(&read("proc/net/route"));
+    interfaces.extend(parse_ipv6_route(&read("proc/net/ipv6_route")));
+
+    let net = root.join("sys/class/net");
+    let kinds = interfaces
+        .iter()
+        .filter_map(|iface| interface_kind(&net, iface))
+        .collect::<Vec<_>>();
+    if kinds.contains(&Connectivity::Wifi) {
+        Connectivity::Wifi
+    } else if kinds.contains(&Connectivity::Mobile) {
+        Connectivity::Mobile
+    } else {
+        Connectivity::None
+    }
+}
+
+/// Asks NetworkManager about the primary connection. Returns `None` if it doesn't know, e.g.
+/// because it's not managing the connection.
+pub fn network_manager() -> Result<Option<Connectivity>, dbus::Error> {
+    let mut bus = SYSTEM_BUS.lock().unwrap_or_else(PoisonError::into_inner);
+    let connection = match bus.take() {
+        // reconnects after the bus was restarted
+        Some(connection) if connection.channel().is_connected() => connection,
+        _ => Connection::new_system()?,
+    };
+    let result = ask_network_manager(&connection);
+    *bus = Some(connection);
+    result
+}
+
+fn ask_network_manager(connection: &Connection) -> Result<Option<Connectivity>, dbus::Error> {
+    let proxy = connection.with_proxy(NM_DESTINATION, NM_PATH, Duration::from_secs(1));
+    let state: u32 = proxy.get(NM_DESTINATION, "State")?;
+    if state <= NM_STATE_DISCONNECTED {
+        return Ok(Some(Connectivity::None));
+    }
+    let connection_type: String = proxy.get(NM_DESTINATION, "PrimaryConnectionType")?;
+    Ok(match connection_type.as_str() {
+        "" => None,
+        "gsm" | "cdma" | "bluetooth" => Some(Connectivity::Mobile),
+        _ => Some(Connectivity::Wifi),
+    })
+}
+
+/// Waits up to `timeout` for the socket to become readable.
+fn readable(fd: libc::c_int, timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// Calls `on_change` whenever links or routes changed, from a new thread. Bursts of messages
+/// are waited out, so it's usually called once per change.
+pub fn watch<F>(mut on_change: F) -> io::Result<()>
+where
+    F: FnMut() + Send + 'static,
+{
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_ROUTE | libc::RTMGRP_IPV6_ROUTE) as u32;
+    let bound = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if bound < 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(err);
+    }
+
+    thread::Builder::new()
+        .name(String::from("connectivity"))
+        .spawn(move || {
+            // the messages aren't parsed, every one of them means something changed
+            let mut buffer = [0u8; 8192];
+            let mut changed = false;
+            loop {
+                if changed {
+                    match readable(fd, WATCH_DEBOUNCE) {
+                        Ok(true) => (),
+                        Ok(false) => {
+                            changed = false;
+                            on_change();
+                            continue;
+                        }
+                        Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(err) => {
+                            warn!("Stopped watching network changes: {}", err);
+                            break;
+                        }
+                    }
+                }
+                let received =
+                    unsafe { libc::recv(fd, buffer.as_mut_ptr() as *mut _, buffer.len(), 0) };
+                if received < 0 {
+                    let err = io::Error::last_os_error();
+                    match err.kind() {
+                        io::ErrorKind::Interrupted => continue,
+                        // ENOBUFS: we missed some messages, which is fine since we re-check
+                        _ if err.raw_os_error() == Some(libc::ENOBUFS) => (),
+                        _ => {
+                            warn!("Stopped watching network changes: {}", err);
+                            break;
+                        }
+                    }
+                }
+                changed = true;
+            }
+            unsafe { libc::close(fd) };
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect;
+    use crate::plugins::connectivity::Connectivity;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/connectivity")
+            .join(name)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&fixture("wifi")), Connectivity::Wifi);
+        assert_eq!(detect(&fixture("ethernet")), Connectivity::Wifi);
+        assert_eq!(detect(&fixture("mobile")), Connectivity::Mobile);
+        assert_eq!(detect(&fixture("none")), Connectivity::None);
+    }
+}
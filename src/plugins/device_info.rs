@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::logging::PluginCallScope;
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::debug;
+
+use self::linux::LinuxDeviceInfo;
+
+mod linux;
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/device_info";
+/// The app runs with `TargetPlatform.android`, so claim a recent enough Android version for it to
+/// enable all features.
+const ANDROID_SDK_INT: i32 = 28;
+
+/// Describes the machine to the app. `getAndroidDeviceInfo` maps it onto what the app expects from
+/// an Android device, `getLinuxDeviceInfo` returns it as is.
+pub struct DeviceInfoPlugin {
+    channel: StandardMethodChannel,
+    info: LinuxDeviceInfo,
+}
+
+fn string(value: &str) -> Value {
+    Value::String(String::from(value))
+}
+
+fn optional_string(value: &Option<String>) -> Value {
+    value
+        .as_ref()
+        .map(|value| string(value))
+        .unwrap_or(Value::Null)
+}
+
+fn map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(key, value)| (string(key), value))
+            .collect::<HashMap<Value, Value>>(),
+    )
+}
+
+/// Translates the architecture to Android ABI names.
+fn android_abis(architecture: &str) -> (Vec<Value>, Vec<Value>) {
+    match architecture {
+        "x86_64" => (vec![string("x86")], vec![string("x86_64")]),
+        "x86" => (vec![string("x86")], vec![]),
+        "aarch64" => (vec![string("armeabi-v7a")], vec![string("arm64-v8a")]),
+        "arm" => (vec![string("armeabi-v7a")], vec![]),
+        other => (vec![], vec![string(other)]),
+    }
+}
+
+impl DeviceInfoPlugin {
+    pub fn new() -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+            info: LinuxDeviceInfo::read(Path::new("/")),
+        }
+    }
+
+    fn get_android_device_info(&self) -> MethodCallResult<Value> {
+        let info = &self.info;
+        let name = match info.os("NAME") {
+            "" => "Linux",
+            name => name,
+        };
+        let version_id = info.os("VERSION_ID");
+        let build_id = match info.os("BUILD_ID") {
+            "" => version_id,
+            build_id => build_id,
+        };
+        let (abis_32, abis_64) = android_abis(&info.architecture);
+        let abis = abis_64.iter().chain(abis_32.iter()).cloned().collect();
+
+        MethodCallResult::Ok(map(vec![
+            (
+                "version",
+                map(vec![
+                    ("baseOS", string(name)),
+                    ("codename", string(info.os("VERSION_CODENAME"))),
+                    ("incremental", string(&info.kernel_release)),
+                    ("previewSdkInt", Value::I32(0)),
+                    ("release", string(version_id)),
+                    ("sdkInt", Value::I32(ANDROID_SDK_INT)),
+                    ("securityPatch", string("")),
+                ]),
+            ),
+            ("board", optional_string(&info.product)),
+            ("bootloader", string("")),
+            ("brand", string(info.os("ID"))),
+            ("device", string(&info.hostname)),
+            ("display", string(info.os("PRETTY_NAME"))),
+            (
+                "fingerprint",
+                string(&format!(
+                    "{}/{}/{}:{}/{}/{}:user/release-keys",
+                    info.os("ID"),
+                    info.os("ID"),
+                    info.architecture,
+                    version_id,
+                    build_id,
+                    info.kernel_release
+                )),
+            ),
+            ("hardware", optional_string(&info.cpu.model)),
+            ("host", string(&info.hostname)),
+            ("id", string(build_id)),
+            (
+                "manufacturer",
+                string(info.vendor.as_ref().map(String::as_str).unwrap_or(name)),
+            ),
+            (
+                "model",
+                string(info.product.as_ref().map(String::as_str).unwrap_or("Linux")),
+            ),
+            ("product", string(info.os("ID"))),
+            ("supported32BitAbis", Value::List(abis_32)),
+            ("supported64BitAbis", Value::List(abis_64)),
+            ("supportedAbis", Value::List(abis)),
+            ("tags", string("release-keys")),
+            ("type", string("user")),
+            ("isPhysicalDevice", Value::Boolean(true)),
+            ("androidId", optional_string(&info.machine_id)),
+        ]))
+    }
+
+    fn get_linux_device_info(&self) -> MethodCallResult<Value> {
+        let info = &self.info;
+        let id_like = info
+            .os("ID_LIKE")
+            .split_whitespace()
+            .map(string)
+            .collect::<Vec<_>>();
+
+        MethodCallResult::Ok(map(vec![
+            ("name", string(info.os("NAME"))),
+            ("version", string(info.os("VERSION"))),
+            ("id", string(info.os("ID"))),
+            ("idLike", Value::List(id_like)),
+            ("versionCodename", string(info.os("VERSION_CODENAME"))),
+            ("versionId", string(info.os("VERSION_ID"))),
+            ("prettyName", string(info.os("PRETTY_NAME"))),
+            ("buildId", string(info.os("BUILD_ID"))),
+            ("variant", string(info.os("VARIANT"))),
+            ("variantId", string(info.os("VARIANT_ID"))),
+            ("machineId", optional_string(&info.machine_id)),
+            ("kernelRelease", string(&info.kernel_release)),
+            ("kernelVersion", string(&info.kernel_version)),
+            ("hostname", string(&info.hostname)),
+            ("architecture", string(&info.architecture)),
+            ("cpuModel", optional_string(&info.cpu.model)),
+            ("cpuCount", Value::I64(info.cpu.count as i64)),
+            (
+                "memoryTotal",
+                info.memory_total
+                    .map(|bytes| Value::I64(bytes as i64))
+                    .unwrap_or(Value::Null),
+            ),
+            ("vendor", optional_string(&info.vendor)),
+            ("product", optional_string(&info.product)),
+        ]))
+    }
+}
+
+impl Plugin for DeviceInfoPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "getAndroidDeviceInfo" => {
+                let response = self.get_android_device_info();
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            "getLinuxDeviceInfo" => {
+                let response = self.get_linux_device_info();
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            _ => (),
+        }
+    }
+}
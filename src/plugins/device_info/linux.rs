@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Salt for the machine id hash, so the raw id never leaves the machine and apps using the same
+/// scheme can't correlate users.
+const MACHINE_ID_SALT: &str = "openbook-desktop";
+
+/// Everything we know about the machine, read from a filesystem root so tests can point it at
+/// fixture files instead of `/`.
+#[derive(Debug, Default)]
+pub struct LinuxDeviceInfo {
+    pub os_release: HashMap<String, String>,
+    pub kernel_release: String,
+    pub kernel_version: String,
+    pub hostname: String,
+    pub architecture: String,
+    pub cpu: CpuInfo,
+    /// Total memory in bytes.
+    pub memory_total: Option<u64>,
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    /// Salted and hashed `/etc/machine-id`.
+    pub machine_id: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CpuInfo {
+    pub model: Option<String>,
+    pub count: usize,
+}
+
+impl LinuxDeviceInfo {
+    pub fn read(root: &Path) -> Self {
+        let read = |paths: &[&str]| {
+            paths
+                .iter()
+                .filter_map(|path| fs::read_to_string(root.join(path)).ok())
+                .next()
+        };
+        let read_line = |paths: &[&str]| {
+            read(paths)
+                .map(|content| String::from(content.trim()))
+                .filter(|content| !content.is_empty())
+        };
+
+        Self {
+            os_release: read(&["etc/os-release", "usr/lib/os-release"])
+                .map(|content| parse_os_release(&content))
+                .unwrap_or_default(),
+            kernel_release: read_line(&["proc/sys/kernel/osrelease"]).unwrap_or_default(),
+            kernel_version: read_line(&["proc/sys/kernel/version"]).unwrap_or_default(),
+            hostname: read_line(&["proc/sys/kernel/hostname", "etc/hostname"]).unwrap_or_default(),
+            architecture: String::from(env::consts::ARCH),
+            cpu: read(&["proc/cpuinfo"])
+                .map(|content| parse_cpuinfo(&content))
+                .unwrap_or_default(),
+            memory_total: read(&["proc/meminfo"]).and_then(|content| parse_meminfo(&content)),
+            vendor: read_line(&["sys/class/dmi/id/sys_vendor"]),
+            product: read_line(&["sys/class/dmi/id/product_name"]),
+            machine_id: read_line(&["etc/machine-id", "var/lib/dbus/machine-id"])
+                .map(|id| hash_machine_id(&id)),
+        }
+    }
+
+    /// Returns a field of `os-release`, or an empty string if it isn't set.
+    pub fn os(&self, key: &str) -> &str {
+        self.os_release.get(key).map(String::as_str).unwrap_or("")
+    }
+}
+
+/// Parses `os-release(5)`: `KEY=value` lines with optional shell-style quoting.
+pub fn parse_os_release(content: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or_default().trim();
+        let value = match parts.next() {
+            Some(value) => unquote(value.trim()),
+            None => continue,
+        };
+        fields.insert(String::from(key), value);
+    }
+    fields
+}
+
+fn unquote(value: &str) -> String {
+    let quote = match value.chars().next() {
+        Some(quote @ '"') | Some(quote @ '\'') if value.len() >= 2 && value.ends_with(quote) => {
+            quote
+        }
+        _ => return String::from(value),
+    };
+    let inner = &value[1..value.len() - 1];
+    if quote == '\'' {
+        return String::from(inner);
+    }
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                unquoted.push(escaped);
+            }
+        } else {
+            unquoted.push(c);
+        }
+    }
+    unquoted
+}
+
+/// Counts processors and finds the CPU model in `/proc/cpuinfo`. x86 has `model name` per
+/// processor, ARM boards usually only have a global `Hardware` or `Model` line.
+pub fn parse_cpuinfo(content: &str) -> CpuInfo {
+    let mut cpu = CpuInfo::default();
+    let mut fallback_model = None;
+    for line in content.lines() {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next().unwrap_or_default().trim();
+        let value = parts.next().unwrap_or_default().trim();
+        match key {
+            "processor" => cpu.count += 1,
+            "model name" if cpu.model.is_none() => cpu.model = Some(String::from(value)),
+            "Hardware" | "Model" if !value.is_empty() => fallback_model = Some(String::from(value)),
+            _ => (),
+        }
+    }
+    if cpu.model.is_none() {
+        cpu.model = fallback_model;
+    }
+    cpu
+}
+
+/// Returns `MemTotal` from `/proc/meminfo` in bytes.
+pub fn parse_meminfo(content: &str) -> Option<u64> {
+    let line = content.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let mut parts = line["MemTotal:".len()..].split_whitespace();
+    let amount = parts.next()?.parse::<u64>().ok()?;
+    match parts.next() {
+        Some("kB") => Some(amount * 1024),
+        None => Some(amount),
+        Some(_) => None,
+    }
+}
+
+/// Derives an id from the machine id: the first 8 bytes of SHA-256 over an app specific salt
+/// and the machine id, as 16 hex digits. That has the format of Android's `ANDROID_ID` and is
+/// stable for this machine, but doesn't reveal the machine id itself.
+pub fn hash_machine_id(machine_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(MACHINE_ID_SALT.as_bytes());
+    hasher.input(b":");
+    hasher.input(machine_id.trim().as_bytes());
+    hasher
+        .result()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_machine_id, parse_cpuinfo, parse_meminfo, parse_os_release, LinuxDeviceInfo};
+    use std::path::PathBuf;
+
+    fn fixture_root() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/device_info")
+    }
+
+    #[test]
+    fn test_parse_os_release_quoting() {
+        let fields = parse_os_release(
+            "# comment\nNAME=\"Fedora\"\nID=fedora\nVARIANT='Workstation Edition'\n\
+             PRETTY_NAME=\"Say \\\"hi\\\"\"\nBROKEN\n",
+        );
+        assert_eq!(fields["NAME"], "Fedora");
+        assert_eq!(fields["ID"], "fedora");
+        assert_eq!(fields["VARIANT"], "Workstation Edition");
+        assert_eq!(fields["PRETTY_NAME"], "Say \"hi\"");
+        assert_eq!(fields.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_cpuinfo_arm() {
+        let cpu = parse_cpuinfo(
+            "processor\t: 0\nBogoMIPS\t: 38.40\n\nprocessor\t: 1\nBogoMIPS\t: 38.40\n\n\
+             Hardware\t: BCM2835\nModel\t\t: Raspberry Pi 3 Model B Rev 1.2\n",
+        );
+        assert_eq!(cpu.count, 2);
+        assert_eq!(
+            cpu.model.as_ref().unwrap(),
+            "Raspberry Pi 3 Model B Rev 1.2"
+        );
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        assert_eq!(parse_meminfo("MemTotal:  2048 kB\n"), Some(2048 * 1024));
+        assert_eq!(parse_meminfo("MemFree:  2048 kB\n"), None);
+    }
+
+    #[test]
+    fn test_read_fixture_root() {
+        let info = LinuxDeviceInfo::read(&fixture_root());
+        assert_eq!(info.os("ID"), "ubuntu");
+        assert_eq!(info.os("PRETTY_NAME"), "Ubuntu 18.04.2 LTS");
+        assert_eq!(info.os("UBUNTU_CODENAME"), "bionic");
+        assert_eq!(info.kernel_release, "4.15.0-46-generic");
+        assert_eq!(info.hostname, "openbook-test");
+        assert_eq!(info.cpu.count, 2);
+        assert_eq!(
+            info.cpu.model.as_ref().unwrap(),
+            "Intel(R) Core(TM) i7-8550U CPU @ 1.80GHz"
+        );
+        assert_eq!(info.memory_total, Some(16_303_428 * 1024));
+        assert_eq!(info.vendor.as_ref().unwrap(), "LENOVO");
+        assert_eq!(info.product.as_ref().unwrap(), "ThinkPad T480s");
+
+        let machine_id = info.machine_id.unwrap();
+        assert_eq!(
+            machine_id,
+            hash_machine_id("0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(machine_id.len(), 16);
+        assert!(!machine_id.contains("0123456789abcdef"));
+    }
+
+    #[test]
+    fn test_read_missing_root() {
+        let info = LinuxDeviceInfo::read(&fixture_root().join("missing"));
+        assert!(info.os_release.is_empty());
+        assert_eq!(info.cpu.count, 0);
+        assert_eq!(info.machine_id, None);
+    }
+}
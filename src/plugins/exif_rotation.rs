@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    images::{self, exif},
+    worker::Worker,
+};
+use crate::{config::ImagesConfig, logging::PluginCallScope};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use image::{GenericImageView, ImageFormat};
+use log::{debug, info};
+
+const CHANNEL_NAME: &str = "flutter_exif_rotation";
+
+/// Rotates a JPEG according to its EXIF orientation and resets the tag. If `strip_metadata` is
+/// set, the EXIF data is dropped completely. Returns `None` if nothing had to be changed.
+fn normalize_jpeg(data: &[u8], strip_metadata: bool) -> Result<Option<Vec<u8>>, String> {
+    let exif_segment = match exif::exif_segment_data(data) {
+        Some(segment) => segment,
+        None => return Ok(None),
+    };
+    let orientation = exif::orientation(exif_segment).unwrap_or(1);
+    if orientation == 1 {
+        // no need to re-encode just to drop the metadata
+        return Ok(if strip_metadata {
+            Some(exif::strip_exif(data))
+        } else {
+            None
+        });
+    }
+
+    let image =
+        image::load_from_memory(data).map_err(|err| format!("Cannot decode image: {}", err))?;
+    let image = images::apply_orientation(image, orientation);
+    let encoded = images::encode(&image, ImageFormat::JPEG, images::DEFAULT_QUALITY)?;
+    Ok(Some(if strip_metadata {
+        encoded
+    } else {
+        let (width, height) = image.dimensions();
+        exif::insert_exif(
+            &encoded,
+            &exif::reset_orientation(exif_segment, width, height),
+        )
+    }))
+}
+
+/// Normalizes the image file in place. Only JPEGs carry EXIF orientations in practice, other
+/// files are left alone.
+fn normalize_file(path: &Path, strip_metadata: bool) -> Result<(), String> {
+    let data = fs::read(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+    match image::guess_format(&data) {
+        Ok(ImageFormat::JPEG) => (),
+        _ => return Ok(()),
+    }
+    if let Some(normalized) = normalize_jpeg(&data, strip_metadata)? {
+        info!("Normalized orientation of {}", path.display());
+        images::replace_file(path, &normalized)
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+fn rotate_image(args: &Value, strip_metadata: bool) -> MethodCallResult<Value> {
+    // there's no gallery to save to, so `save` is ignored
+    let result = match super::map_value(args, "path") {
+        Some(Value::String(path)) => {
+            normalize_file(Path::new(path), strip_metadata).map(|_| Value::String(path.clone()))
+        }
+        _ => Err(String::from("path is missing")),
+    };
+    match result {
+        Ok(path) => MethodCallResult::Ok(path),
+        Err(message) => MethodCallResult::Err {
+            details: Value::Null,
+            code: String::from(""),
+            message,
+        },
+    }
+}
+
+/// Applies EXIF orientations of picked photos to their pixels, since the app shows and uploads
+/// them as they are stored. Decoding and encoding runs on a worker thread.
+pub struct ExifRotationPlugin {
+    channel: Arc<StandardMethodChannel>,
+    strip_metadata: bool,
+    worker: Worker,
+}
+
+impl ExifRotationPlugin {
+    pub fn new(config: &ImagesConfig) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            strip_metadata: config.strip_metadata,
+            worker: Worker::new("exif-rotation"),
+        }
+    }
+}
+
+impl Plugin for ExifRotationPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "rotateImage" | "rotateAndSaveImage" => {
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                let (args, strip_metadata) = (decoded.args, self.strip_metadata);
+                self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+                    let response = rotate_image(&args, strip_metadata);
+                    channel.send_method_call_response(response_handle, response);
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exif, normalize_file};
+    use crate::testing::TempDir;
+    use image::GenericImageView;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn fixture(orientation: u16) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/exif_rotation")
+            .join(format!("orientation_{}.jpg", orientation))
+    }
+
+    /// The fixtures show red, green, blue and white quadrants from top left to bottom right once
+    /// their orientation is applied.
+    fn assert_upright(path: &PathBuf, orientation: u16) {
+        let image = image::open(path).unwrap();
+        assert_eq!(image.dimensions(), (32, 16), "orientation {}", orientation);
+        for &(x, y, expected) in &[
+            (8, 4, [255, 0, 0]),
+            (24, 4, [0, 255, 0]),
+            (8, 12, [0, 0, 255]),
+            (24, 12, [255, 255, 255]),
+        ] {
+            let pixel = image.get_pixel(x, y).data;
+            for channel in 0..3 {
+                assert!(
+                    (i32::from(pixel[channel]) - expected[channel]).abs() < 48,
+                    "orientation {}: pixel at {},{} is {:?}",
+                    orientation,
+                    x,
+                    y,
+                    pixel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_all_orientations() {
+        let dir = TempDir::new("exif");
+
+        for orientation in 1..=8 {
+            let original = fs::read(fixture(orientation)).unwrap();
+            assert_eq!(
+                exif::exif_segment_data(&original).and_then(exif::orientation),
+                Some(orientation)
+            );
+
+            let path = dir.join(format!("keep_{}.jpg", orientation));
+            fs::copy(fixture(orientation), &path).unwrap();
+            normalize_file(&path, false).unwrap();
+            assert_upright(&path, orientation);
+            let data = fs::read(&path).unwrap();
+            let segment = exif::exif_segment_data(&data).expect("EXIF data was removed");
+            assert_eq!(exif::orientation(segment), Some(1));
+            // everything but the orientation is kept
+            assert_eq!(
+                segment.len(),
+                exif::exif_segment_data(&original).unwrap().len()
+            );
+
+            let path = dir.join(format!("strip_{}.jpg", orientation));
+            fs::copy(fixture(orientation), &path).unwrap();
+            normalize_file(&path, true).unwrap();
+            assert_upright(&path, orientation);
+            assert!(exif::exif_segment_data(&fs::read(&path).unwrap()).is_none());
+        }
+    }
+}
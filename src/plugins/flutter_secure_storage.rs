@@ -1,16 +1,16 @@
 use std::collections::HashMap;
-use std::fs::File;
 use std::sync::Arc;
 
 use self::crypto::Crypto;
-use super::DecodeError;
+use super::{json_store::JsonStore, DecodeError};
+use crate::{logging::PluginCallScope, profile::Profile};
 
 use flutter_engine::{
     channel::{Channel, StandardMethodChannel},
     codec::{standard_codec::Value, MethodCallResult},
     FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
 };
-use log::{debug, info, trace};
+use log::{debug, error, info, trace};
 
 mod crypto;
 
@@ -34,45 +34,29 @@ plugin_args! {
 pub struct FlutterSecureStoragePlugin {
     channel: StandardMethodChannel,
     storage: HashMap<String, String>,
+    store: JsonStore,
     crypto: Crypto,
 }
 
 impl FlutterSecureStoragePlugin {
-    pub fn new() -> Self {
-        let mut storage = match dirs::data_dir() {
-            Some(dir) => match File::open(dir.join("openbook").join(STORAGE_FILE_NAME)) {
-                Ok(file) => match serde_json::from_reader(file) {
-                    Ok(result) => result,
-                    _ => HashMap::new(),
-                },
-                _ => HashMap::new(),
-            },
-            None => HashMap::new(),
-        };
+    pub fn new(profile: &Profile) -> Self {
+        let store = JsonStore::new(profile, STORAGE_FILE_NAME);
+        let mut storage = store.load();
 
         let crypto = Crypto::from_storage(&mut storage).expect("Failed to create crypto");
 
-        Self::save(&storage);
+        if let Err(err) = store.save(&storage) {
+            error!("Cannot save the secure storage key: {}", err);
+        }
 
         Self {
             channel: StandardMethodChannel::new(CHANNEL_NAME),
             storage,
+            store,
             crypto,
         }
     }
 
-    fn save(storage: &HashMap<String, String>) {
-        match serde_json::to_string(storage) {
-            Ok(data) => match dirs::data_dir() {
-                Some(dir) => {
-                    std::fs::write(dir.join("openbook").join(STORAGE_FILE_NAME), data).ok()
-                }
-                None => None,
-            },
-            _ => None,
-        };
-    }
-
     fn read(&self, args: &ReadArgs) -> MethodCallResult<Value> {
         trace!("Read key {}", args.key);
 
@@ -96,9 +80,14 @@ impl FlutterSecureStoragePlugin {
             Ok(data) => {
                 self.storage.insert(String::from(args.key), data);
 
-                Self::save(&self.storage);
-
-                MethodCallResult::Ok(Value::Null)
+                match self.store.save(&self.storage) {
+                    Ok(()) => MethodCallResult::Ok(Value::Null),
+                    Err(err) => MethodCallResult::Err {
+                        details: Value::Null,
+                        code: String::from(""),
+                        message: format!("Cannot save {}: {}", args.key, err),
+                    },
+                }
             }
             _ => MethodCallResult::Err {
                 details: Value::Null,
@@ -152,6 +141,7 @@ impl Plugin for FlutterSecureStoragePlugin {
         _window: &mut Window,
     ) {
         let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
 
         debug!(
             "Got method call {} with args: {}",
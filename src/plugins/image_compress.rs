@@ -0,0 +1,379 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::images::{self, exif};
+use super::worker::Worker;
+use crate::logging::PluginCallScope;
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use log::{debug, warn};
+
+const CHANNEL_NAME: &str = "flutter_image_compress";
+
+/// Output formats in the order of the Dart `CompressFormat` enum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompressFormat {
+    Jpeg,
+    Png,
+    Heic,
+    Webp,
+}
+
+impl CompressFormat {
+    fn from_index(index: i64) -> Option<Self> {
+        match index {
+            0 => Some(CompressFormat::Jpeg),
+            1 => Some(CompressFormat::Png),
+            2 => Some(CompressFormat::Heic),
+            3 => Some(CompressFormat::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Options shared by all methods. They're sent as a positional list, so the parsing depends on
+/// the method.
+#[derive(Debug, PartialEq)]
+struct CompressOptions {
+    min_width: u32,
+    min_height: u32,
+    quality: u8,
+    rotate: i64,
+    auto_correction_angle: bool,
+    format: CompressFormat,
+    keep_exif: bool,
+}
+
+enum Source {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// A decoded method call, ready to be run on the worker thread.
+struct CompressRequest {
+    source: Source,
+    target_path: Option<PathBuf>,
+    options: CompressOptions,
+}
+
+fn int_arg(args: &[Value], index: usize) -> Result<i64, String> {
+    match args.get(index) {
+        Some(Value::I32(value)) => Ok(i64::from(*value)),
+        Some(Value::I64(value)) => Ok(*value),
+        _ => Err(format!("Argument {} must be an integer", index)),
+    }
+}
+
+fn bool_arg(args: &[Value], index: usize) -> Result<bool, String> {
+    match args.get(index) {
+        Some(Value::Boolean(value)) => Ok(*value),
+        _ => Err(format!("Argument {} must be a boolean", index)),
+    }
+}
+
+fn path_arg(args: &[Value], index: usize) -> Result<PathBuf, String> {
+    match args.get(index) {
+        Some(Value::String(path)) => Ok(PathBuf::from(path)),
+        _ => Err(format!("Argument {} must be a path", index)),
+    }
+}
+
+impl CompressRequest {
+    fn from_call(method: &str, args: &Value) -> Result<Self, String> {
+        let args = match args {
+            Value::List(args) => args.as_slice(),
+            _ => return Err(String::from("Arguments must be a list")),
+        };
+        let size =
+            |index| int_arg(args, index).map(|size| size.max(1).min(i64::from(u32::MAX)) as u32);
+        // compressAndGetFile has the target path in the middle of the options
+        let (source, target_path, rest) = match method {
+            "compressWithList" => match args.first() {
+                Some(Value::U8List(data)) => (Source::Bytes(data.clone()), None, 4),
+                _ => return Err(String::from("Argument 0 must be a byte list")),
+            },
+            "compressWithFile" => (Source::File(path_arg(args, 0)?), None, 4),
+            "compressAndGetFile" => (
+                Source::File(path_arg(args, 0)?),
+                Some(path_arg(args, 4)?),
+                5,
+            ),
+            _ => return Err(format!("Unknown method {}", method)),
+        };
+        let format = int_arg(args, rest + 2)?;
+        Ok(Self {
+            source,
+            target_path,
+            options: CompressOptions {
+                min_width: size(1)?,
+                min_height: size(2)?,
+                quality: int_arg(args, 3)?.max(1).min(100) as u8,
+                rotate: int_arg(args, rest)?,
+                auto_correction_angle: bool_arg(args, rest + 1)?,
+                format: CompressFormat::from_index(format)
+                    .ok_or_else(|| format!("Unknown format {}", format))?,
+                keep_exif: bool_arg(args, rest + 3)?,
+            },
+        })
+    }
+
+    fn run(self) -> Result<Value, String> {
+        let data = match self.source {
+            Source::Bytes(data) => data,
+            Source::File(path) => {
+                fs::read(&path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))?
+            }
+        };
+        let compressed = compress(&data, &self.options)?;
+        match self.target_path {
+            Some(path) => {
+                fs::write(&path, compressed)
+                    .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+                Ok(Value::String(path.to_string_lossy().into_owned()))
+            }
+            None => Ok(Value::U8List(compressed)),
+        }
+    }
+}
+
+/// Size after scaling down like the Android plugin: by the smaller factor that keeps the image
+/// at least `min_width` wide or `min_height` high, never up.
+fn target_size(width: u32, height: u32, min_width: u32, min_height: u32) -> (u32, u32) {
+    let scale = (f64::from(width) / f64::from(min_width))
+        .min(f64::from(height) / f64::from(min_height))
+        .max(1.0);
+    (
+        ((f64::from(width) / scale).round() as u32).max(1),
+        ((f64::from(height) / scale).round() as u32).max(1),
+    )
+}
+
+fn rotate(image: DynamicImage, degrees: i64) -> DynamicImage {
+    match degrees.rem_euclid(360) {
+        0 => image,
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        other => {
+            warn!("Cannot rotate by {} degrees, only multiples of 90", other);
+            image
+        }
+    }
+}
+
+fn compress(data: &[u8], options: &CompressOptions) -> Result<Vec<u8>, String> {
+    let image =
+        image::load_from_memory(data).map_err(|err| format!("Cannot decode image: {}", err))?;
+    let exif_segment = match image::guess_format(data) {
+        Ok(ImageFormat::JPEG) => exif::exif_segment_data(data),
+        _ => None,
+    };
+    let image = match exif_segment.and_then(exif::orientation) {
+        Some(orientation) if options.auto_correction_angle => {
+            images::apply_orientation(image, orientation)
+        }
+        _ => image,
+    };
+
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = target_size(width, height, options.min_width, options.min_height);
+    let image = if (new_width, new_height) == (width, height) {
+        image
+    } else {
+        image.resize_exact(new_width, new_height, image::FilterType::Lanczos3)
+    };
+    let image = rotate(image, options.rotate);
+
+    match options.format {
+        CompressFormat::Jpeg => {
+            let encoded = images::encode(&image, ImageFormat::JPEG, options.quality)?;
+            // like on Android, the orientation isn't kept since the pixels may have been turned
+            Ok(match exif_segment {
+                Some(segment) if options.keep_exif => {
+                    let (width, height) = image.dimensions();
+                    exif::insert_exif(&encoded, &exif::reset_orientation(segment, width, height))
+                }
+                _ => encoded,
+            })
+        }
+        CompressFormat::Png => images::encode(&image, ImageFormat::PNG, options.quality),
+        CompressFormat::Webp => images::encode(&image, ImageFormat::WEBP, options.quality),
+        CompressFormat::Heic => Err(String::from("HEIC is not supported on desktop")),
+    }
+}
+
+/// Compresses images for upload. The work runs on a worker thread, the response is sent from
+/// there once it's done.
+pub struct ImageCompressPlugin {
+    channel: Arc<StandardMethodChannel>,
+    worker: Worker,
+}
+
+impl ImageCompressPlugin {
+    pub fn new() -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            worker: Worker::new("image-compress"),
+        }
+    }
+}
+
+impl Plugin for ImageCompressPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "compressWithList" | "compressWithFile" | "compressAndGetFile" => {
+                let request = CompressRequest::from_call(&decoded.method, &decoded.args);
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+                    let response = match request.and_then(CompressRequest::run) {
+                        Ok(value) => MethodCallResult::Ok(value),
+                        Err(message) => MethodCallResult::Err {
+                            details: Value::Null,
+                            code: String::from(""),
+                            message,
+                        },
+                    };
+                    channel.send_method_call_response(response_handle, response);
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, exif, target_size, CompressFormat, CompressOptions, CompressRequest};
+    use flutter_engine::codec::standard_codec::Value;
+    use image::{GenericImageView, ImageFormat};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A 32x16 photo stored sideways with orientation 6.
+    fn sideways_photo() -> Vec<u8> {
+        fs::read(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/exif_rotation/orientation_6.jpg"),
+        )
+        .unwrap()
+    }
+
+    fn options(format: CompressFormat) -> CompressOptions {
+        CompressOptions {
+            min_width: 16,
+            min_height: 8,
+            quality: 90,
+            rotate: 0,
+            auto_correction_angle: true,
+            format,
+            keep_exif: false,
+        }
+    }
+
+    #[test]
+    fn test_target_size() {
+        assert_eq!(target_size(4000, 3000, 1920, 1080), (1920, 1440));
+        assert_eq!(target_size(3000, 4000, 1920, 1080), (1920, 2560));
+        assert_eq!(target_size(800, 600, 1920, 1080), (800, 600));
+    }
+
+    #[test]
+    fn test_from_call() {
+        let args = Value::List(vec![
+            Value::String(String::from("/tmp/in.jpg")),
+            Value::I32(1920),
+            Value::I32(1080),
+            Value::I32(95),
+            Value::String(String::from("/tmp/out.webp")),
+            Value::I32(-90),
+            Value::Boolean(true),
+            Value::I32(3),
+            Value::Boolean(false),
+        ]);
+        let request = CompressRequest::from_call("compressAndGetFile", &args).unwrap();
+        assert_eq!(request.target_path, Some(PathBuf::from("/tmp/out.webp")));
+        assert_eq!(
+            request.options,
+            CompressOptions {
+                min_width: 1920,
+                min_height: 1080,
+                quality: 95,
+                rotate: -90,
+                auto_correction_angle: true,
+                format: CompressFormat::Webp,
+                keep_exif: false,
+            }
+        );
+        assert!(CompressRequest::from_call("compressWithList", &args).is_err());
+    }
+
+    #[test]
+    fn test_compress() {
+        let photo = sideways_photo();
+
+        let jpeg = compress(&photo, &options(CompressFormat::Jpeg)).unwrap();
+        assert_eq!(image::guess_format(&jpeg).unwrap(), ImageFormat::JPEG);
+        assert_eq!(
+            image::load_from_memory(&jpeg).unwrap().dimensions(),
+            (16, 8)
+        );
+        assert!(exif::exif_segment_data(&jpeg).is_none());
+
+        let kept = compress(
+            &photo,
+            &CompressOptions {
+                keep_exif: true,
+                ..options(CompressFormat::Jpeg)
+            },
+        )
+        .unwrap();
+        let segment = exif::exif_segment_data(&kept).expect("EXIF data was removed");
+        assert_eq!(exif::orientation(segment), Some(1));
+
+        let rotated = compress(
+            &photo,
+            &CompressOptions {
+                rotate: 270,
+                auto_correction_angle: false,
+                ..options(CompressFormat::Png)
+            },
+        )
+        .unwrap();
+        assert_eq!(image::guess_format(&rotated).unwrap(), ImageFormat::PNG);
+        // stored as 16x32, which is too narrow to scale down, then turned back
+        assert_eq!(
+            image::load_from_memory(&rotated).unwrap().dimensions(),
+            (32, 16)
+        );
+
+        let webp = compress(&photo, &options(CompressFormat::Webp)).unwrap();
+        assert!(webp.starts_with(b"RIFF") && &webp[8..12] == b"WEBP");
+
+        assert!(compress(&photo, &options(CompressFormat::Heic)).is_err());
+    }
+}
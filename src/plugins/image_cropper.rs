@@ -0,0 +1,272 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{images, worker::Worker};
+use crate::{logging::PluginCallScope, profile::Profile};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use image::ImageFormat;
+use log::debug;
+
+const CHANNEL_NAME: &str = "plugins.hunghd.vn/image_cropper";
+const FILE_PREFIX: &str = "image_cropper";
+/// Same default as the Android plugin.
+const DEFAULT_QUALITY: u8 = 90;
+
+/// Arguments of `cropImage`. The plugin also sends lots of settings for the cropping UI, which
+/// we don't have.
+struct CropOptions {
+    source_path: PathBuf,
+    ratio: Option<(f64, f64)>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    format: ImageFormat,
+    quality: u8,
+}
+
+fn number(args: &Value, key: &str) -> Option<f64> {
+    match super::map_value(args, key) {
+        Some(Value::F64(number)) => Some(*number),
+        Some(Value::I32(number)) => Some(f64::from(*number)),
+        Some(Value::I64(number)) => Some(*number as f64),
+        _ => None,
+    }
+}
+
+impl CropOptions {
+    fn from_value(args: &Value) -> Option<Self> {
+        let source_path = match super::map_value(args, "source_path") {
+            Some(Value::String(path)) => PathBuf::from(path),
+            _ => return None,
+        };
+        let size = |key| {
+            number(args, key)
+                .filter(|size| *size >= 1.0)
+                .map(|size| size.round() as u32)
+        };
+        Some(Self {
+            source_path,
+            ratio: match (number(args, "ratio_x"), number(args, "ratio_y")) {
+                (Some(x), Some(y)) if x > 0.0 && y > 0.0 => Some((x, y)),
+                _ => None,
+            },
+            max_width: size("max_width"),
+            max_height: size("max_height"),
+            format: match super::map_value(args, "compress_format") {
+                Some(Value::String(format)) if format == "png" => ImageFormat::PNG,
+                _ => ImageFormat::JPEG,
+            },
+            quality: number(args, "compress_quality")
+                .filter(|quality| *quality >= 1.0 && *quality <= 100.0)
+                .map_or(DEFAULT_QUALITY, |quality| quality as u8),
+        })
+    }
+}
+
+/// Crops images without showing any UI: the largest area with the requested aspect ratio is cut
+/// out of the center, then it's scaled down to the maximum size. Decoding and encoding runs on a
+/// worker thread.
+pub struct ImageCropperPlugin {
+    channel: Arc<StandardMethodChannel>,
+    temp_dir: Option<PathBuf>,
+    worker: Worker,
+}
+
+fn error_result(message: String) -> MethodCallResult<Value> {
+    MethodCallResult::Err {
+        details: Value::Null,
+        code: String::from(""),
+        message,
+    }
+}
+
+/// Crops the source image and writes the result to a new file in `dir`.
+fn crop_to_file(options: &CropOptions, dir: &Path) -> Result<PathBuf, String> {
+    let image = image::open(&options.source_path)
+        .map_err(|err| format!("Cannot open {}: {}", options.source_path.display(), err))?;
+    let image = match options.ratio {
+        Some((x, y)) => images::center_crop(image, x, y),
+        None => image,
+    };
+    let image = images::scale_down(image, options.max_width, options.max_height);
+    let data = images::encode(&image, options.format, options.quality)?;
+
+    let (path, mut file) =
+        images::create_temp_file(dir, FILE_PREFIX, images::extension(options.format))
+            .map_err(|err| format!("Cannot create file: {}", err))?;
+    file.write_all(&data)
+        .map_err(|err| format!("Cannot write image: {}", err))?;
+    Ok(path)
+}
+
+fn crop_image(args: &Value, temp_dir: Option<&Path>) -> MethodCallResult<Value> {
+    let options = match CropOptions::from_value(args) {
+        Some(options) => options,
+        None => return error_result(String::from("source_path is missing")),
+    };
+    let temp_dir = match temp_dir {
+        Some(dir) => dir,
+        None => return error_result(String::from("No temp directory")),
+    };
+    match crop_to_file(&options, temp_dir) {
+        Ok(path) => MethodCallResult::Ok(Value::String(path.to_string_lossy().into_owned())),
+        Err(err) => error_result(err),
+    }
+}
+
+impl ImageCropperPlugin {
+    pub fn new(profile: &Profile) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            temp_dir: profile.temp_dir(),
+            worker: Worker::new("image-cropper"),
+        }
+    }
+}
+
+impl Plugin for ImageCropperPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "cropImage" => {
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                let (args, temp_dir) = (decoded.args, self.temp_dir.clone());
+                self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+                    let response = crop_image(&args, temp_dir.as_deref());
+                    channel.send_method_call_response(response_handle, response);
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crop_image;
+    use crate::testing::TempDir;
+    use flutter_engine::codec::{standard_codec::Value, MethodCallResult};
+    use image::{GenericImageView, ImageBuffer, Rgb};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Set this to write the current output as new golden images.
+    const UPDATE_GOLDEN_VAR: &str = "OPENBOOK_UPDATE_GOLDEN";
+
+    fn golden_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/image_cropper")
+    }
+
+    /// Four colored quadrants with a gradient, so a crop from the wrong place is easy to spot.
+    fn write_source(path: &Path) {
+        ImageBuffer::from_fn(300, 200, |x, y| {
+            let shade = ((x + y) % 64) as u8;
+            match (x < 150, y < 100) {
+                (true, true) => Rgb([255, shade, shade]),
+                (false, true) => Rgb([shade, 255, shade]),
+                (true, false) => Rgb([shade, shade, 255]),
+                (false, false) => Rgb([255, 255, shade]),
+            }
+        })
+        .save(path)
+        .unwrap();
+    }
+
+    fn crop(temp_dir: &Path, args: Vec<(&str, Value)>) -> PathBuf {
+        let args = Value::Map(
+            args.into_iter()
+                .map(|(key, value)| (Value::String(String::from(key)), value))
+                .collect::<HashMap<_, _>>(),
+        );
+        match crop_image(&args, Some(temp_dir)) {
+            MethodCallResult::Ok(Value::String(path)) => PathBuf::from(path),
+            _ => panic!("cropImage failed"),
+        }
+    }
+
+    fn assert_golden(path: &Path, name: &str) {
+        let golden = golden_dir().join(name);
+        if env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+            fs::copy(path, &golden).unwrap();
+        }
+        let actual = image::open(path).unwrap().to_rgba();
+        let expected = image::open(&golden).unwrap().to_rgba();
+        assert_eq!(actual.dimensions(), expected.dimensions(), "{}", name);
+        assert!(actual.into_raw() == expected.into_raw(), "{} differs", name);
+    }
+
+    #[test]
+    fn test_crop_golden() {
+        let dir = TempDir::new("cropper");
+        let source = dir.join("source.png");
+        write_source(&source);
+        let source_path = Value::String(source.to_string_lossy().into_owned());
+        let temp_dir = dir.join("temp");
+
+        let square = crop(
+            &temp_dir,
+            vec![
+                ("source_path", source_path.clone()),
+                ("ratio_x", Value::F64(1.0)),
+                ("ratio_y", Value::F64(1.0)),
+                ("max_width", Value::Null),
+                ("max_height", Value::Null),
+                ("compress_format", Value::String(String::from("png"))),
+                ("compress_quality", Value::I32(90)),
+            ],
+        );
+        assert!(square.starts_with(&temp_dir));
+        assert_golden(&square, "square.png");
+
+        let banner = crop(
+            &temp_dir,
+            vec![
+                ("source_path", source_path.clone()),
+                ("ratio_x", Value::F64(3.0)),
+                ("ratio_y", Value::F64(1.0)),
+                ("max_width", Value::I32(150)),
+                ("compress_format", Value::String(String::from("png"))),
+            ],
+        );
+        assert_golden(&banner, "banner.png");
+
+        // JPEG isn't compared pixel by pixel, encoders may differ slightly
+        let jpeg = crop(
+            &temp_dir,
+            vec![
+                ("source_path", source_path),
+                ("ratio_x", Value::F64(1.0)),
+                ("ratio_y", Value::F64(1.0)),
+                ("max_width", Value::F64(64.0)),
+                ("compress_quality", Value::I32(50)),
+            ],
+        );
+        assert_eq!(jpeg.extension().unwrap(), "jpg");
+        assert_eq!(image::open(&jpeg).unwrap().dimensions(), (64, 64));
+    }
+}
@@ -0,0 +1,255 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use self::dialog::{FileDialog, MediaKind, SystemDialog};
+use super::{images, worker::Worker};
+use crate::{logging::PluginCallScope, profile::Profile};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, info};
+
+mod dialog;
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/image_picker";
+const FILE_PREFIX: &str = "image_picker";
+
+/// Options of `pickImage`, all of them can be missing or `null`.
+#[derive(Default)]
+struct ImageOptions {
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    quality: Option<u8>,
+}
+
+impl ImageOptions {
+    fn from_value(args: &Value) -> Self {
+        let size = |key| match super::map_value(args, key) {
+            Some(Value::F64(size)) if *size > 0.0 => Some(size.round() as u32),
+            Some(Value::I32(size)) if *size > 0 => Some(*size as u32),
+            _ => None,
+        };
+        Self {
+            max_width: size("maxWidth"),
+            max_height: size("maxHeight"),
+            quality: match super::map_value(args, "imageQuality") {
+                Some(Value::I32(quality)) if *quality > 0 && *quality <= 100 => {
+                    Some(*quality as u8)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max_width.is_none() && self.max_height.is_none() && self.quality.is_none()
+    }
+}
+
+/// Picks images and videos with a file chooser. The app may delete what it gets once it's
+/// uploaded, so it always gets a copy in the temp directory and never the user's file. The
+/// chooser runs on a worker thread, so the window keeps drawing while it's open.
+pub struct ImagePickerPlugin {
+    channel: Arc<StandardMethodChannel>,
+    picker: Arc<Picker>,
+    worker: Worker,
+}
+
+struct Picker {
+    dialog: Box<dyn FileDialog>,
+    temp_dir: Option<PathBuf>,
+}
+
+fn error_result(message: String) -> MethodCallResult<Value> {
+    MethodCallResult::Err {
+        details: Value::Null,
+        code: String::from(""),
+        message,
+    }
+}
+
+/// Decodes the image, applies the options and writes it to a new file in `dir`.
+fn process_image(source: &Path, options: &ImageOptions, dir: &Path) -> Result<PathBuf, String> {
+    let data = fs::read(source).map_err(|err| format!("Cannot read image: {}", err))?;
+    let format = image::guess_format(&data).map_err(|err| format!("Cannot read image: {}", err))?;
+    let image =
+        image::load_from_memory(&data).map_err(|err| format!("Cannot decode image: {}", err))?;
+    let image = images::scale_down(image, options.max_width, options.max_height);
+    let data = images::encode(
+        &image,
+        format,
+        options.quality.unwrap_or(images::DEFAULT_QUALITY),
+    )?;
+
+    let (path, mut file) = images::create_temp_file(dir, FILE_PREFIX, images::extension(format))
+        .map_err(|err| format!("Cannot create file: {}", err))?;
+    file.write_all(&data)
+        .map_err(|err| format!("Cannot write image: {}", err))?;
+    Ok(path)
+}
+
+fn copy_file(source: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let extension = source
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    let (path, _) = images::create_temp_file(dir, FILE_PREFIX, extension)?;
+    fs::copy(source, &path)?;
+    Ok(path)
+}
+
+impl ImagePickerPlugin {
+    pub fn new(profile: &Profile) -> Self {
+        Self::with_dialog(Box::new(SystemDialog), profile.temp_dir())
+    }
+
+    fn with_dialog(dialog: Box<dyn FileDialog>, temp_dir: Option<PathBuf>) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            picker: Arc::new(Picker { dialog, temp_dir }),
+            worker: Worker::new("image-picker"),
+        }
+    }
+}
+
+impl Picker {
+    fn pick(&self, kind: MediaKind, args: &Value) -> MethodCallResult<Value> {
+        // there is no camera support, so the camera source gets the file chooser as well
+        if let Some(Value::I32(0)) = super::map_value(args, "source") {
+            info!("Camera is not available, showing the file chooser instead");
+        }
+        let temp_dir = match self.temp_dir.as_ref() {
+            Some(dir) => dir,
+            None => return error_result(String::from("No temp directory")),
+        };
+        let source = match self.dialog.pick(kind) {
+            Ok(Some(path)) => path,
+            Ok(None) => return MethodCallResult::Ok(Value::Null),
+            Err(err) => return error_result(err),
+        };
+        debug!("Picked {}", source.display());
+
+        let options = match kind {
+            MediaKind::Image => ImageOptions::from_value(args),
+            MediaKind::Video => ImageOptions::default(),
+        };
+        let result = if options.is_empty() {
+            copy_file(&source, temp_dir).map_err(|err| format!("Cannot copy file: {}", err))
+        } else {
+            process_image(&source, &options, temp_dir)
+        };
+        match result {
+            Ok(path) => MethodCallResult::Ok(Value::String(path.to_string_lossy().into_owned())),
+            Err(err) => error_result(err),
+        }
+    }
+}
+
+impl Plugin for ImagePickerPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        let kind = match decoded.method.as_str() {
+            "pickImage" => MediaKind::Image,
+            "pickVideo" => MediaKind::Video,
+            _ => return,
+        };
+        let picker = self.picker.clone();
+        let channel = self.channel.clone();
+        let response_handle = msg.response_handle;
+        let args = decoded.args;
+        self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+            let response = picker.pick(kind, &args);
+            channel.send_method_call_response(response_handle, response);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dialog::{FileDialog, MediaKind};
+    use super::ImagePickerPlugin;
+    use crate::testing::TempDir;
+    use flutter_engine::codec::{standard_codec::Value, MethodCallResult};
+    use image::{GenericImageView, ImageBuffer, Rgb};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Always "selects" the same file.
+    struct FixedDialog(Option<PathBuf>);
+
+    impl FileDialog for FixedDialog {
+        fn pick(&self, _kind: MediaKind) -> Result<Option<PathBuf>, String> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn pick_image(plugin: &ImagePickerPlugin, args: Vec<(&str, Value)>) -> Option<PathBuf> {
+        let args = Value::Map(
+            args.into_iter()
+                .map(|(key, value)| (Value::String(String::from(key)), value))
+                .collect::<HashMap<_, _>>(),
+        );
+        match plugin.picker.pick(MediaKind::Image, &args) {
+            MethodCallResult::Ok(Value::String(path)) => Some(PathBuf::from(path)),
+            MethodCallResult::Ok(Value::Null) => None,
+            _ => panic!("pickImage failed"),
+        }
+    }
+
+    #[test]
+    fn test_pick_image() {
+        let dir = TempDir::new("picker");
+        let source = dir.join("source.png");
+        ImageBuffer::from_fn(400, 200, |x, _| Rgb([(x % 256) as u8, 0, 0]))
+            .save(&source)
+            .unwrap();
+        let temp_dir = dir.join("temp");
+        let plugin = ImagePickerPlugin::with_dialog(
+            Box::new(FixedDialog(Some(source.clone()))),
+            Some(temp_dir.to_path_buf()),
+        );
+
+        // without options the file is copied unchanged
+        let copy = pick_image(&plugin, vec![("source", Value::I32(1))]).unwrap();
+        assert!(copy.starts_with(&temp_dir));
+        assert_eq!(fs::read(&copy).unwrap(), fs::read(&source).unwrap());
+
+        let resized = pick_image(
+            &plugin,
+            vec![
+                ("source", Value::I32(1)),
+                ("maxWidth", Value::F64(100.0)),
+                ("maxHeight", Value::Null),
+            ],
+        )
+        .unwrap();
+        assert_ne!(resized, copy);
+        assert_eq!(image::open(&resized).unwrap().dimensions(), (100, 50));
+
+        let plugin = ImagePickerPlugin::with_dialog(Box::new(FixedDialog(None)), Some(temp_dir));
+        assert_eq!(pick_image(&plugin, vec![("source", Value::I32(1))]), None);
+    }
+}
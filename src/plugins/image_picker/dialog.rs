@@ -0,0 +1,177 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+use log::info;
+
+/// Gives up on a file chooser that doesn't answer. Users get plenty of time to choose.
+#[cfg(target_os = "linux")]
+const DIALOG_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaKind {
+    Image,
+    Video,
+}
+
+impl MediaKind {
+    fn title(self) -> &'static str {
+        match self {
+            MediaKind::Image => "Select an image",
+            MediaKind::Video => "Select a video",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            MediaKind::Image => "image/*",
+            MediaKind::Video => "video/*",
+        }
+    }
+
+    /// Glob patterns for choosers that can't filter by MIME type.
+    fn patterns(self) -> &'static str {
+        match self {
+            MediaKind::Image => "*.jpg *.jpeg *.png *.gif *.webp *.bmp",
+            MediaKind::Video => "*.mp4 *.m4v *.mov *.webm *.mkv *.avi",
+        }
+    }
+}
+
+/// Lets the user choose a file. Returns `None` if they cancelled.
+pub trait FileDialog: Send + Sync {
+    fn pick(&self, kind: MediaKind) -> Result<Option<PathBuf>, String>;
+}
+
+/// Uses the FileChooser portal and falls back to zenity or kdialog if there is none.
+pub struct SystemDialog;
+
+impl FileDialog for SystemDialog {
+    fn pick(&self, kind: MediaKind) -> Result<Option<PathBuf>, String> {
+        #[cfg(target_os = "linux")]
+        match pick_with_portal(kind) {
+            Ok(path) => return Ok(path),
+            Err(err) => info!("File chooser portal not available: {}", err),
+        }
+
+        for (program, args) in &[
+            (
+                "zenity",
+                vec![
+                    String::from("--file-selection"),
+                    format!("--title={}", kind.title()),
+                    format!("--file-filter={} | {}", kind.title(), kind.patterns()),
+                ],
+            ),
+            (
+                "kdialog",
+                vec![
+                    String::from("--title"),
+                    String::from(kind.title()),
+                    String::from("--getopenfilename"),
+                    String::from("."),
+                    format!("{}|{}", kind.patterns(), kind.title()),
+                ],
+            ),
+        ] {
+            match Command::new(program).args(args).output() {
+                Ok(output) => {
+                    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                    // both exit with 1 when cancelled
+                    return match output.status.code() {
+                        Some(0) if !path.is_empty() => Ok(Some(PathBuf::from(path))),
+                        Some(0) | Some(1) => Ok(None),
+                        _ => Err(format!("{} failed with {}", program, output.status)),
+                    };
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                    info!("{} not available", program)
+                }
+                Err(err) => return Err(format!("Cannot run {}: {}", program, err)),
+            }
+        }
+        Err(String::from(
+            "No file chooser available, install xdg-desktop-portal, zenity or kdialog",
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pick_with_portal(kind: MediaKind) -> Result<Option<PathBuf>, dbus::Error> {
+    use dbus::arg::{RefArg, Variant};
+
+    let results = crate::portal::request(
+        "org.freedesktop.portal.FileChooser",
+        "OpenFile",
+        DIALOG_TIMEOUT,
+        |mut options| {
+            // filters are a(sa(us)), 1 means the pattern is a MIME type
+            let filters = vec![(
+                String::from(kind.title()),
+                vec![(1u32, String::from(kind.mime_type()))],
+            )];
+            options.insert(
+                String::from("filters"),
+                Variant(Box::new(filters) as Box<dyn RefArg>),
+            );
+            options.insert(
+                String::from("modal"),
+                Variant(Box::new(true) as Box<dyn RefArg>),
+            );
+            ("", kind.title(), options)
+        },
+    )?;
+    Ok(results.and_then(|results| {
+        let uri = results
+            .get("uris")?
+            .0
+            .as_iter()?
+            .next()?
+            .as_str()?
+            .to_string();
+        uri_to_path(&uri)
+    }))
+}
+
+/// Converts a `file://` URI from the portal to a path.
+#[cfg(target_os = "linux")]
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let path = uri.trim_start_matches("file://");
+    if path.len() == uri.len() {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte == b'%' && rest.len() >= 2 {
+            if let Ok(decoded) = u8::from_str_radix(&String::from_utf8_lossy(&rest[..2]), 16) {
+                bytes.push(decoded);
+                rest = &rest[2..];
+                continue;
+            }
+        }
+        bytes.push(byte);
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::uri_to_path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///home/user/My%20Pictures/%C3%A4.jpg"),
+            Some(PathBuf::from("/home/user/My Pictures/ä.jpg"))
+        );
+        assert_eq!(uri_to_path("https://example.com/a.jpg"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+
+pub mod exif;
+
+/// Quality used for JPEGs when the app doesn't ask for one.
+pub const DEFAULT_QUALITY: u8 = 100;
+
+/// Returns the largest size with the same aspect ratio that fits into the given bounds. Images
+/// are never scaled up.
+pub fn fit_within(
+    width: u32,
+    height: u32,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> (u32, u32) {
+    let max_width = max_width.unwrap_or(width).min(width).max(1);
+    let max_height = max_height.unwrap_or(height).min(height).max(1);
+    let scale =
+        (f64::from(max_width) / f64::from(width)).min(f64::from(max_height) / f64::from(height));
+    (
+        ((f64::from(width) * scale).round() as u32).max(1),
+        ((f64::from(height) * scale).round() as u32).max(1),
+    )
+}
+
+/// Scales the image down to fit into the given bounds.
+pub fn scale_down(
+    image: DynamicImage,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = fit_within(width, height, max_width, max_height);
+    if (new_width, new_height) == (width, height) {
+        image
+    } else {
+        image.resize_exact(new_width, new_height, image::FilterType::Lanczos3)
+    }
+}
+
+/// Cuts the largest area with the given aspect ratio out of the center of the image.
+pub fn center_crop(mut image: DynamicImage, ratio_x: f64, ratio_y: f64) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    if ratio_x <= 0.0 || ratio_y <= 0.0 {
+        return image;
+    }
+    let ratio = ratio_x / ratio_y;
+    let (crop_width, crop_height) = if f64::from(width) / f64::from(height) > ratio {
+        ((f64::from(height) * ratio).round() as u32, height)
+    } else {
+        (width, (f64::from(width) / ratio).round() as u32)
+    };
+    let (crop_width, crop_height) = (crop_width.max(1).min(width), crop_height.max(1).min(height));
+    if (crop_width, crop_height) == (width, height) {
+        return image;
+    }
+    image.crop(
+        (width - crop_width) / 2,
+        (height - crop_height) / 2,
+        crop_width,
+        crop_height,
+    )
+}
+
+/// Turns the pixels so the image looks right without its EXIF orientation.
+pub fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Encodes the image as JPEG or WebP with the given quality, or as PNG for every other format
+/// since that's the only other lossless format we can write.
+pub fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>, String> {
+    let quality = quality.max(1).min(100);
+    let output = match format {
+        ImageFormat::JPEG => ImageOutputFormat::JPEG(quality),
+        ImageFormat::WEBP => {
+            // image can only decode WebP
+            let rgba = image.to_rgba();
+            let (width, height) = rgba.dimensions();
+            // fails for images larger than 16383 pixels in either direction
+            let encoded = webp::Encoder::from_rgba(&rgba, width, height)
+                .encode_simple(false, f32::from(quality))
+                .map_err(|err| format!("Cannot encode image: {:?}", err))?;
+            return Ok(encoded.to_vec());
+        }
+        _ => ImageOutputFormat::PNG,
+    };
+    let mut data = Vec::new();
+    image
+        .write_to(&mut data, output)
+        .map_err(|err| format!("Cannot encode image: {}", err))?;
+    Ok(data)
+}
+
+/// File extension for what `encode` writes in the given format.
+pub fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::JPEG => "jpg",
+        ImageFormat::WEBP => "webp",
+        _ => "png",
+    }
+}
+
+/// Creates a new empty file named `<prefix>_<timestamp>_<n>.<extension>` in the directory.
+pub fn create_temp_file(dir: &Path, prefix: &str, extension: &str) -> io::Result<(PathBuf, File)> {
+    fs::create_dir_all(dir)?;
+    let timestamp = Local::now().format("%Y%m%d%H%M%S");
+    let mut n = 0;
+    loop {
+        let path = dir.join(format!("{}_{}_{}.{}", prefix, timestamp, n, extension));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Replaces the content of a file by writing a temporary file next to it and renaming it over
+/// the old one, so a crash never leaves a half written image behind.
+pub fn replace_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{center_crop, encode, fit_within};
+    use image::{DynamicImage, GenericImageView, ImageFormat};
+
+    #[test]
+    fn test_fit_within() {
+        assert_eq!(fit_within(400, 200, None, None), (400, 200));
+        assert_eq!(fit_within(400, 200, Some(100), None), (100, 50));
+        assert_eq!(fit_within(400, 200, None, Some(100)), (200, 100));
+        assert_eq!(fit_within(400, 200, Some(100), Some(100)), (100, 50));
+        assert_eq!(fit_within(400, 200, Some(1000), Some(1000)), (400, 200));
+        assert_eq!(fit_within(4000, 1, Some(100), None), (100, 1));
+    }
+
+    #[test]
+    fn test_center_crop() {
+        let image = DynamicImage::new_rgb8(400, 200);
+        assert_eq!(
+            center_crop(image.clone(), 1.0, 1.0).dimensions(),
+            (200, 200)
+        );
+        assert_eq!(
+            center_crop(image.clone(), 4.0, 1.0).dimensions(),
+            (400, 100)
+        );
+        assert_eq!(
+            center_crop(image.clone(), 2.0, 1.0).dimensions(),
+            (400, 200)
+        );
+        assert_eq!(center_crop(image, 0.0, 1.0).dimensions(), (400, 200));
+    }
+
+    #[test]
+    fn test_encode_webp_too_large() {
+        assert!(encode(&DynamicImage::new_rgb8(16, 16), ImageFormat::WEBP, 80).is_ok());
+        assert!(encode(&DynamicImage::new_rgb8(16384, 1), ImageFormat::WEBP, 80).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::ops::Range;
+
+const SOI: [u8; 2] = [0xFF, 0xD8];
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const SOS: u8 = 0xDA;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+const EXIF_IFD_TAG: u16 = 0x8769;
+const PIXEL_X_DIMENSION_TAG: u16 = 0xA002;
+const PIXEL_Y_DIMENSION_TAG: u16 = 0xA003;
+const SHORT_TYPE: u16 = 3;
+const LONG_TYPE: u16 = 4;
+
+/// A marker segment of a JPEG file, `range` covers the marker, length and payload.
+struct Segment {
+    marker: u8,
+    range: Range<usize>,
+}
+
+/// Walks the segments up to the start of the image data.
+fn segments(jpeg: &[u8]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    if !jpeg.starts_with(&SOI) {
+        return segments;
+    }
+    let mut pos = SOI.len();
+    while pos + 4 <= jpeg.len() && jpeg[pos] == 0xFF {
+        let marker = jpeg[pos + 1];
+        if marker == SOS {
+            break;
+        }
+        let length = usize::from(u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]));
+        let end = pos + 2 + length;
+        if length < 2 || end > jpeg.len() {
+            break;
+        }
+        segments.push(Segment {
+            marker,
+            range: pos..end,
+        });
+        pos = end;
+    }
+    segments
+}
+
+fn exif_segment(jpeg: &[u8]) -> Option<Segment> {
+    segments(jpeg).into_iter().find(|segment| {
+        segment.marker == APP1 && jpeg[segment.range.start + 4..].starts_with(EXIF_HEADER)
+    })
+}
+
+/// Returns the complete Exif APP1 segment of the JPEG, if it has one.
+pub fn exif_segment_data(jpeg: &[u8]) -> Option<&[u8]> {
+    exif_segment(jpeg).map(|segment| &jpeg[segment.range])
+}
+
+/// Returns the JPEG without its Exif segment.
+pub fn strip_exif(jpeg: &[u8]) -> Vec<u8> {
+    match exif_segment(jpeg) {
+        Some(segment) => [&jpeg[..segment.range.start], &jpeg[segment.range.end..]].concat(),
+        None => jpeg.to_vec(),
+    }
+}
+
+/// Inserts an Exif segment after the JFIF header (or the start of image if there is none).
+pub fn insert_exif(jpeg: &[u8], exif_segment: &[u8]) -> Vec<u8> {
+    let pos = segments(jpeg)
+        .first()
+        .filter(|segment| segment.marker == APP0)
+        .map_or(SOI.len(), |segment| segment.range.end);
+    [&jpeg[..pos], exif_segment, &jpeg[pos..]].concat()
+}
+
+/// Reads integers from TIFF data in its byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..2)? {
+            b"MM" => true,
+            b"II" => false,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16(&self, pos: usize) -> Option<u16> {
+        let bytes = [*self.data.get(pos)?, *self.data.get(pos + 1)?];
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, pos: usize) -> Option<u32> {
+        let bytes = [
+            *self.data.get(pos)?,
+            *self.data.get(pos + 1)?,
+            *self.data.get(pos + 2)?,
+            *self.data.get(pos + 3)?,
+        ];
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Finds an entry with the given tag in the IFD at `ifd` and returns its type and the
+    /// position of its value.
+    fn entry(&self, ifd: usize, tag: u16) -> Option<(u16, usize)> {
+        let count = usize::from(self.u16(ifd)?);
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .find(|&entry| self.u16(entry) == Some(tag))
+            .and_then(|entry| Some((self.u16(entry + 2)?, entry + 8)))
+    }
+
+    fn ifd0(&self) -> Option<usize> {
+        Some(self.u32(4)? as usize)
+    }
+
+    /// Position of the offset to IFD1, which holds the thumbnail.
+    fn next_ifd_pos(&self, ifd: usize) -> Option<usize> {
+        Some(ifd + 2 + usize::from(self.u16(ifd)?) * 12)
+    }
+
+    fn exif_ifd(&self) -> Option<usize> {
+        match self.entry(self.ifd0()?, EXIF_IFD_TAG)? {
+            (LONG_TYPE, pos) => Some(self.u32(pos)? as usize),
+            _ => None,
+        }
+    }
+
+    /// Finds the orientation entry in IFD0 and returns its value and the position of the value.
+    fn orientation(&self) -> Option<(u16, usize)> {
+        match self.entry(self.ifd0()?, ORIENTATION_TAG)? {
+            (SHORT_TYPE, pos) => Some((self.u16(pos)?, pos)),
+            _ => None,
+        }
+    }
+}
+
+/// Writes integers into TIFF data in its byte order.
+struct TiffWriter<'a> {
+    data: &'a mut [u8],
+    big_endian: bool,
+}
+
+impl<'a> TiffWriter<'a> {
+    fn put(&mut self, pos: usize, bytes: &[u8]) {
+        if let Some(target) = self.data.get_mut(pos..pos + bytes.len()) {
+            target.copy_from_slice(bytes);
+        }
+    }
+
+    fn put_u16(&mut self, pos: usize, value: u16) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.put(pos, &bytes);
+    }
+
+    fn put_u32(&mut self, pos: usize, value: u32) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.put(pos, &bytes);
+    }
+
+    /// Sets a SHORT or LONG entry, other types are left alone.
+    fn put_dimension(&mut self, entry: Option<(u16, usize)>, value: u32) {
+        match entry {
+            Some((SHORT_TYPE, pos)) if value <= u32::from(u16::MAX) => {
+                self.put_u16(pos, value as u16)
+            }
+            Some((LONG_TYPE, pos)) => self.put_u32(pos, value),
+            _ => (),
+        }
+    }
+}
+
+/// Range of the TIFF data inside an Exif segment.
+fn tiff_range(segment: &[u8]) -> Range<usize> {
+    4 + EXIF_HEADER.len()..segment.len()
+}
+
+/// Returns the orientation from an Exif segment, 1 to 8 if it's valid.
+pub fn orientation(exif_segment: &[u8]) -> Option<u16> {
+    Tiff::new(&exif_segment[tiff_range(exif_segment)])?
+        .orientation()
+        .map(|(orientation, _)| orientation)
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Returns a copy of the Exif segment for an image whose orientation was applied to its pixels,
+/// now `width`×`height`. The orientation is set to 1 so viewers don't rotate the pixels again,
+/// the pixel dimensions are updated and the thumbnail in IFD1, which still shows the old
+/// orientation, is dropped.
+pub fn reset_orientation(exif_segment: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut segment = exif_segment.to_vec();
+    let range = tiff_range(exif_segment);
+    let reader = match Tiff::new(&exif_segment[range.clone()]) {
+        Some(reader) => reader,
+        None => return segment,
+    };
+    let orientation = reader.orientation().map(|(_, pos)| pos);
+    let next_ifd = reader.ifd0().and_then(|ifd| reader.next_ifd_pos(ifd));
+    let exif_ifd = reader.exif_ifd();
+    let pixel_x = exif_ifd.and_then(|ifd| reader.entry(ifd, PIXEL_X_DIMENSION_TAG));
+    let pixel_y = exif_ifd.and_then(|ifd| reader.entry(ifd, PIXEL_Y_DIMENSION_TAG));
+
+    let mut writer = TiffWriter {
+        data: &mut segment[range],
+        big_endian: reader.big_endian,
+    };
+    if let Some(pos) = orientation {
+        writer.put_u16(pos, 1);
+    }
+    // the thumbnail's data stays in the segment, but nothing refers to it anymore
+    if let Some(pos) = next_ifd {
+        writer.put_u32(pos, 0);
+    }
+    writer.put_dimension(pixel_x, width);
+    writer.put_dimension(pixel_y, height);
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{orientation, reset_orientation, Tiff, TiffWriter, EXIF_HEADER};
+
+    /// Builds an Exif segment with IFD0 (orientation, Exif IFD pointer), an Exif IFD with the
+    /// pixel dimensions and an IFD1 offset.
+    fn segment(width: u16, height: u32) -> Vec<u8> {
+        let mut tiff = vec![0u8; 64];
+        tiff[..4].copy_from_slice(b"II*\0");
+        let mut writer = TiffWriter {
+            data: &mut tiff,
+            big_endian: false,
+        };
+        writer.put_u32(4, 8);
+        // IFD0 at 8 with 2 entries, the next IFD offset at 34
+        writer.put_u16(8, 2);
+        for (i, &(tag, kind, value)) in [(0x0112u16, 3u16, 6u32), (0x8769, 4, 38)]
+            .iter()
+            .enumerate()
+        {
+            let entry = 10 + i * 12;
+            writer.put_u16(entry, tag);
+            writer.put_u16(entry + 2, kind);
+            writer.put_u32(entry + 4, 1);
+            writer.put_u32(entry + 8, value);
+        }
+        writer.put_u32(34, 60);
+        // Exif IFD at 38 with PixelXDimension as SHORT and PixelYDimension as LONG
+        writer.put_u16(38, 2);
+        writer.put_u16(40, 0xA002);
+        writer.put_u16(42, 3);
+        writer.put_u32(44, 1);
+        writer.put_u16(48, width);
+        writer.put_u16(52, 0xA003);
+        writer.put_u16(54, 4);
+        writer.put_u32(56, 1);
+        writer.put_u32(60, height);
+
+        let length = (2 + EXIF_HEADER.len() + tiff.len()) as u16;
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&length.to_be_bytes());
+        segment.extend_from_slice(EXIF_HEADER);
+        segment.extend_from_slice(&tiff);
+        segment
+    }
+
+    #[test]
+    fn test_reset_orientation() {
+        let original = segment(400, 300);
+        assert_eq!(orientation(&original), Some(6));
+
+        let reset = reset_orientation(&original, 300, 400);
+        assert_eq!(orientation(&reset), Some(1));
+        let tiff = Tiff::new(&reset[10..]).unwrap();
+        let exif_ifd = tiff.exif_ifd().unwrap();
+        let (_, pixel_x) = tiff.entry(exif_ifd, 0xA002).unwrap();
+        let (_, pixel_y) = tiff.entry(exif_ifd, 0xA003).unwrap();
+        assert_eq!(tiff.u16(pixel_x), Some(300));
+        assert_eq!(tiff.u32(pixel_y), Some(400));
+        assert_eq!(tiff.u32(34), Some(0));
+    }
+}
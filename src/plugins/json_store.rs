@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::profile::Profile;
+
+use chrono::Local;
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A JSON file in the profile's data directory that plugins keep their state in.
+pub struct JsonStore {
+    path: Option<PathBuf>,
+}
+
+impl JsonStore {
+    pub fn new(profile: &Profile, file_name: &str) -> Self {
+        Self {
+            path: profile.data_dir().map(|dir| dir.join(file_name)),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_path(path: PathBuf) -> Self {
+        Self { path: Some(path) }
+    }
+
+    /// Loads the stored data, or the default if there is none. A file that can't be parsed is
+    /// moved aside first, so saving the default doesn't overwrite what's left in it.
+    pub fn load<T: DeserializeOwned + Default>(&self) -> T {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return T::default(),
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return T::default(),
+        };
+        match serde_json::from_reader(file) {
+            Ok(data) => data,
+            Err(err) => {
+                let mut broken_name = path.file_name().unwrap_or_default().to_os_string();
+                broken_name.push(format!(".broken-{}", Local::now().format("%Y%m%d-%H%M%S")));
+                let broken_path = path.with_file_name(broken_name);
+                match fs::rename(path, &broken_path) {
+                    Ok(()) => error!(
+                        "Cannot parse {}, moved it to {}: {}",
+                        path.display(),
+                        broken_path.display(),
+                        err
+                    ),
+                    Err(rename_err) => error!(
+                        "Cannot parse {}: {}. Cannot move it aside either: {}",
+                        path.display(),
+                        err,
+                        rename_err
+                    ),
+                }
+                T::default()
+            }
+        }
+    }
+
+    /// Writes the data to a temporary file next to the store and renames it over the old one, so
+    /// a crash while saving never leaves a truncated file behind.
+    pub fn save<T: Serialize>(&self, data: &T) -> io::Result<()> {
+        match self.path.as_ref() {
+            Some(path) => Self::write_atomic(path, data).map_err(|err| {
+                warn!("Cannot save {}: {}", path.display(), err);
+                err
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn write_atomic<T: Serialize>(path: &Path, data: &T) -> io::Result<()> {
+        let data = serde_json::to_vec(data)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonStore;
+    use crate::testing::TempDir;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new("store");
+        let store = JsonStore::with_path(dir.join("store.json"));
+
+        assert!(store.load::<HashMap<String, String>>().is_empty());
+
+        let mut data = HashMap::new();
+        data.insert(String::from("key"), String::from("value"));
+        store.save(&data).unwrap();
+        assert_eq!(store.load::<HashMap<String, String>>(), data);
+        assert!(!dir.join("store.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_unparsable_file_is_kept() {
+        let dir = TempDir::new("store-broken");
+        let store = JsonStore::with_path(dir.join("store.json"));
+        fs::write(dir.join("store.json"), "{\"key\": null").unwrap();
+
+        assert!(store.load::<HashMap<String, String>>().is_empty());
+        assert!(!dir.join("store.json").exists());
+        let broken = fs::read_dir(&*dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("store.json.broken-"))
+            .expect("the unparsable file wasn't moved aside");
+        assert_eq!(fs::read_to_string(broken).unwrap(), "{\"key\": null");
+
+        store.save(&HashMap::<String, String>::new()).unwrap();
+        assert!(store.load::<HashMap<String, String>>().is_empty());
+    }
+}
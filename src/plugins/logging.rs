@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use super::DecodeError;
+use crate::logging::PluginCallScope;
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{log, Level};
+
+const CHANNEL_NAME: &str = "openbook_desktop/logging";
+const TARGET_PREFIX: &str = "dart";
+
+plugin_args! {
+    LogArgs,
+    level: Value, "level", v @ Value::I32(_) => v, v @ Value::String(_) => v;
+    logger: str, "logger", Value::String(v) => v.as_str();
+    message: str, "message", Value::String(v) => v.as_str();
+}
+
+/// Receives log records from the Dart side and passes them on to the `log` crate under a
+/// `dart::<logger>` target.
+pub struct LoggingPlugin {
+    channel: StandardMethodChannel,
+}
+
+/// Maps levels of Dart's `logging` package, either by value or by name.
+fn dart_level(level: &Value) -> Level {
+    match level {
+        Value::I32(value) if *value >= 1000 => Level::Error,
+        Value::I32(value) if *value >= 900 => Level::Warn,
+        Value::I32(value) if *value >= 800 => Level::Info,
+        Value::I32(value) if *value >= 500 => Level::Debug,
+        Value::I32(_) => Level::Trace,
+        Value::String(name) => match name.to_uppercase().as_str() {
+            "SHOUT" | "SEVERE" | "ERROR" => Level::Error,
+            "WARNING" | "WARN" => Level::Warn,
+            "INFO" => Level::Info,
+            "CONFIG" | "FINE" | "DEBUG" => Level::Debug,
+            _ => Level::Trace,
+        },
+        _ => Level::Info,
+    }
+}
+
+impl LoggingPlugin {
+    pub fn new() -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+        }
+    }
+
+    fn log(&self, args: &LogArgs) -> MethodCallResult<Value> {
+        let target = if args.logger.is_empty() {
+            String::from(TARGET_PREFIX)
+        } else {
+            format!("{}::{}", TARGET_PREFIX, args.logger)
+        };
+        log!(target: &target, dart_level(args.level), "{}", args.message);
+        MethodCallResult::Ok(Value::Null)
+    }
+}
+
+impl Plugin for LoggingPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        // no debug output of the arguments here, every record would be logged twice
+        match decoded.method.as_str() {
+            "log" => {
+                let response = match LogArgs::from_value(&decoded.args) {
+                    Ok(args) => self.log(&args),
+                    Err(_) => MethodCallResult::Err {
+                        details: Value::Null,
+                        code: String::from(""),
+                        message: String::from(""),
+                    },
+                };
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{build_info, logging::PluginCallScope};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, warn};
+use serde_derive::Deserialize;
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/package_info";
+const VERSION_FILE_NAME: &str = "version.json";
+
+/// Version data written into the flutter bundle by newer flutter tools.
+#[derive(Deserialize)]
+struct BundleVersion {
+    app_name: Option<String>,
+    package_name: Option<String>,
+    version: Option<String>,
+    build_number: Option<String>,
+}
+
+pub struct PackageInfoPlugin {
+    channel: StandardMethodChannel,
+    app_name: String,
+    package_name: String,
+    version: String,
+    build_number: String,
+}
+
+/// Splits a pubspec version like `0.0.46+46` into version and build number.
+fn split_version(version: &str) -> (String, String) {
+    let mut parts = version.splitn(2, '+');
+    let name = parts.next().unwrap_or_default();
+    let build_number = parts.next().unwrap_or_default();
+    (String::from(name), String::from(build_number))
+}
+
+impl PackageInfoPlugin {
+    pub fn new(assets_path: &Path) -> Self {
+        let (version, build_number) = split_version(build_info::APP_VERSION);
+        let mut plugin = Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+            app_name: String::from(build_info::APP_NAME),
+            package_name: String::from(build_info::PACKAGE_NAME),
+            version,
+            build_number,
+        };
+
+        // the bundle is more accurate than the build time data if it's there
+        if let Ok(file) = File::open(assets_path.join(VERSION_FILE_NAME)) {
+            match serde_json::from_reader::<_, BundleVersion>(file) {
+                Ok(bundle) => {
+                    plugin.app_name = bundle.app_name.unwrap_or(plugin.app_name);
+                    plugin.package_name = bundle.package_name.unwrap_or(plugin.package_name);
+                    plugin.version = bundle.version.unwrap_or(plugin.version);
+                    plugin.build_number = bundle.build_number.unwrap_or(plugin.build_number);
+                }
+                Err(err) => warn!("Cannot parse {} from bundle: {}", VERSION_FILE_NAME, err),
+            }
+        }
+        plugin
+    }
+
+    fn get_all(&self) -> MethodCallResult<Value> {
+        let mut map = HashMap::<Value, Value>::new();
+        for (key, value) in &[
+            ("appName", &self.app_name),
+            ("packageName", &self.package_name),
+            ("version", &self.version),
+            ("buildNumber", &self.build_number),
+        ] {
+            map.insert(
+                Value::String(String::from(*key)),
+                Value::String(String::clone(value)),
+            );
+        }
+        MethodCallResult::Ok(Value::Map(map))
+    }
+}
+
+impl Plugin for PackageInfoPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "getAll" => {
+                let response = self.get_all();
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use std::{fs, path::PathBuf};
 
+use crate::{logging::PluginCallScope, profile::Profile};
+
 use flutter_engine::{
     channel::{Channel, StandardMethodChannel},
     codec::{standard_codec::Value, MethodCallResult},
@@ -12,30 +14,27 @@ const CHANNEL_NAME: &str = "plugins.flutter.io/path_provider";
 
 pub struct PathProviderPlugin {
     channel: StandardMethodChannel,
+    profile: Profile,
 }
 
 impl PathProviderPlugin {
-    pub fn new() -> Self {
+    pub fn new(profile: &Profile) -> Self {
         Self {
             channel: StandardMethodChannel::new(CHANNEL_NAME),
+            profile: profile.clone(),
         }
     }
 
-    fn get_directory_result(&self, dir: Option<PathBuf>, subdir: bool) -> MethodCallResult<Value> {
+    fn get_directory_result(&self, dir: Option<PathBuf>) -> MethodCallResult<Value> {
         match dir {
-            Some(dir) => {
-                let dir = if subdir { dir.join("openbook") } else { dir };
-                match fs::create_dir_all(&dir) {
-                    Ok(_) => {
-                        MethodCallResult::Ok(Value::String(dir.to_string_lossy().into_owned()))
-                    }
-                    Err(_) => MethodCallResult::Err {
-                        details: Value::Null,
-                        code: String::from(""),
-                        message: String::from(""),
-                    },
-                }
-            }
+            Some(dir) => match fs::create_dir_all(&dir) {
+                Ok(_) => MethodCallResult::Ok(Value::String(dir.to_string_lossy().into_owned())),
+                Err(_) => MethodCallResult::Err {
+                    details: Value::Null,
+                    code: String::from(""),
+                    message: String::from(""),
+                },
+            },
             None => MethodCallResult::Err {
                 details: Value::Null,
                 code: String::from(""),
@@ -45,15 +44,15 @@ impl PathProviderPlugin {
     }
 
     fn get_temporary_directory(&self) -> MethodCallResult<Value> {
-        self.get_directory_result(dirs::cache_dir(), true)
+        self.get_directory_result(self.profile.temp_dir())
     }
 
     fn get_application_documents_directory(&self) -> MethodCallResult<Value> {
-        self.get_directory_result(dirs::data_dir(), true)
+        self.get_directory_result(self.profile.data_dir())
     }
 
     fn get_storage_directory(&self) -> MethodCallResult<Value> {
-        self.get_directory_result(dirs::home_dir(), false)
+        self.get_directory_result(dirs::home_dir())
     }
 }
 
@@ -70,6 +69,7 @@ impl Plugin for PathProviderPlugin {
         _window: &mut Window,
     ) {
         let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
 
         debug!(
             "Got method call {} with args: {}",
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use crate::logging::PluginCallScope;
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::debug;
+
+const CHANNEL_NAME: &str = "openbook_desktop/sentry";
+
+/// Tells the Dart side which DSN to use so Sentry reports end up at the local endpoint.
+pub struct SentryPlugin {
+    channel: StandardMethodChannel,
+    dsn: Option<String>,
+}
+
+impl SentryPlugin {
+    pub fn new(dsn: Option<String>) -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+            dsn,
+        }
+    }
+
+    fn get_dsn(&self) -> MethodCallResult<Value> {
+        match &self.dsn {
+            Some(dsn) => MethodCallResult::Ok(Value::String(dsn.clone())),
+            None => MethodCallResult::Ok(Value::Null),
+        }
+    }
+}
+
+impl Plugin for SentryPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "getDsn" => {
+                let response = self.get_dsn();
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            _ => (),
+        }
+    }
+}
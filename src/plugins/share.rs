@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use self::desktop::{Desktop, SystemDesktop};
+use super::worker::Worker;
+use crate::{
+    config::{ShareConfig, ShareHandler},
+    logging::PluginCallScope,
+};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, info, warn};
+
+mod desktop;
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/share";
+/// Longer texts are cut in the confirmation.
+const PREVIEW_LENGTH: usize = 80;
+
+/// Shares text by copying it to the clipboard, since there's no share sheet on desktop. It can
+/// also be sent to a mail program or a custom command. The clipboard tools run on a worker
+/// thread, so a hanging one doesn't freeze the UI.
+pub struct SharePlugin {
+    channel: Arc<StandardMethodChannel>,
+    sharer: Arc<Sharer>,
+    worker: Worker,
+}
+
+struct Sharer {
+    handler: Option<ShareHandler>,
+    desktop: Box<dyn Desktop>,
+}
+
+fn preview(text: &str) -> String {
+    let mut chars = text.chars();
+    let preview = chars.by_ref().take(PREVIEW_LENGTH).collect::<String>();
+    if chars.next().is_some() {
+        format!("{}…", preview)
+    } else {
+        preview
+    }
+}
+
+impl SharePlugin {
+    pub fn new(config: &ShareConfig) -> Self {
+        Self::with_desktop(Box::new(SystemDesktop), config.handler.clone())
+    }
+
+    fn with_desktop(desktop: Box<dyn Desktop>, handler: Option<ShareHandler>) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            sharer: Arc::new(Sharer { handler, desktop }),
+            worker: Worker::new("share"),
+        }
+    }
+}
+
+impl Sharer {
+    fn handler_command(&self, text: &str, subject: Option<&str>) -> Option<Vec<String>> {
+        match self.handler.as_ref()? {
+            ShareHandler::Email => {
+                let mut command = vec![String::from("xdg-email")];
+                if let Some(subject) = subject {
+                    command.extend(vec![String::from("--subject"), String::from(subject)]);
+                }
+                command.extend(vec![String::from("--body"), String::from(text)]);
+                Some(command)
+            }
+            ShareHandler::Command(command) if !command.is_empty() => {
+                let mut command = command.clone();
+                command.push(String::from(text));
+                Some(command)
+            }
+            ShareHandler::Command(_) => None,
+        }
+    }
+
+    /// Shares the text. It only fails if it didn't get anywhere.
+    fn share(&self, text: &str, subject: Option<&str>) -> Result<(), String> {
+        let copied = self.desktop.set_clipboard(text);
+        if let Err(err) = &copied {
+            warn!("Cannot copy shared text: {}", err);
+        }
+        let handled = self.handler_command(text, subject).map(|command| {
+            info!("Sharing with {}", command[0]);
+            self.desktop.spawn(&command)
+        });
+        if let Some(Err(err)) = &handled {
+            warn!("Cannot share: {}", err);
+        }
+
+        let summary = match (&copied, &handled) {
+            (Ok(()), Some(Ok(()))) => "Copied to clipboard and shared",
+            (Ok(()), _) => "Copied to clipboard",
+            (Err(_), Some(Ok(()))) => "Shared",
+            (Err(err), _) => return Err(err.clone()),
+        };
+        self.desktop.notify(summary, &preview(text));
+        Ok(())
+    }
+}
+
+impl Plugin for SharePlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        if decoded.method != "share" {
+            return;
+        }
+        let channel = self.channel.clone();
+        let sharer = self.sharer.clone();
+        let response_handle = msg.response_handle;
+        let args = decoded.args;
+        self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+            // the origin of the share sheet is only used on iPad
+            let subject = match super::map_value(&args, "subject") {
+                Some(Value::String(subject)) => Some(subject.as_str()),
+                _ => None,
+            };
+            let result = match super::map_value(&args, "text") {
+                Some(Value::String(text)) if !text.is_empty() => sharer.share(text, subject),
+                _ => Err(String::from("Non-empty text expected")),
+            };
+            let response = match result {
+                Ok(()) => MethodCallResult::Ok(Value::Null),
+                Err(message) => MethodCallResult::Err {
+                    details: Value::Null,
+                    code: String::from(""),
+                    message,
+                },
+            };
+            channel.send_method_call_response(response_handle, response);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::desktop::Desktop;
+    use super::{preview, SharePlugin, PREVIEW_LENGTH};
+    use crate::config::ShareHandler;
+    use std::sync::{Arc, Mutex};
+
+    /// Records everything instead of touching the desktop.
+    #[derive(Default)]
+    struct Recorded {
+        clipboard: Vec<String>,
+        spawned: Vec<Vec<String>>,
+        notifications: Vec<String>,
+    }
+
+    struct FakeDesktop {
+        recorded: Arc<Mutex<Recorded>>,
+        clipboard_works: bool,
+    }
+
+    impl Desktop for FakeDesktop {
+        fn set_clipboard(&self, text: &str) -> Result<(), String> {
+            if !self.clipboard_works {
+                return Err(String::from("No clipboard"));
+            }
+            self.recorded
+                .lock()
+                .unwrap()
+                .clipboard
+                .push(String::from(text));
+            Ok(())
+        }
+
+        fn spawn(&self, command: &[String]) -> Result<(), String> {
+            self.recorded.lock().unwrap().spawned.push(command.to_vec());
+            Ok(())
+        }
+
+        fn notify(&self, summary: &str, _body: &str) {
+            self.recorded
+                .lock()
+                .unwrap()
+                .notifications
+                .push(String::from(summary));
+        }
+    }
+
+    fn plugin(
+        handler: Option<ShareHandler>,
+        clipboard_works: bool,
+    ) -> (SharePlugin, Arc<Mutex<Recorded>>) {
+        let recorded = Arc::new(Mutex::new(Recorded::default()));
+        let desktop = FakeDesktop {
+            recorded: recorded.clone(),
+            clipboard_works,
+        };
+        (
+            SharePlugin::with_desktop(Box::new(desktop), handler),
+            recorded,
+        )
+    }
+
+    #[test]
+    fn test_share() {
+        let text = "Check out this post https://openbook.social/p/1";
+
+        let (clipboard_only, recorded) = plugin(None, true);
+        clipboard_only.sharer.share(text, None).unwrap();
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.clipboard, vec![String::from(text)]);
+        assert!(recorded.spawned.is_empty());
+        assert_eq!(
+            recorded.notifications,
+            vec![String::from("Copied to clipboard")]
+        );
+
+        let (email, recorded) = plugin(Some(ShareHandler::Email), true);
+        email.sharer.share(text, Some("A post")).unwrap();
+        assert_eq!(
+            recorded.lock().unwrap().spawned,
+            vec![vec![
+                String::from("xdg-email"),
+                String::from("--subject"),
+                String::from("A post"),
+                String::from("--body"),
+                String::from(text),
+            ]]
+        );
+
+        let command = ShareHandler::Command(vec![String::from("notes"), String::from("--add")]);
+        let (custom, recorded) = plugin(Some(command), false);
+        custom.sharer.share(text, None).unwrap();
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(
+            recorded.spawned,
+            vec![vec![
+                String::from("notes"),
+                String::from("--add"),
+                String::from(text)
+            ]]
+        );
+        assert_eq!(recorded.notifications, vec![String::from("Shared")]);
+
+        let (broken, recorded) = plugin(None, false);
+        assert!(broken.sharer.share(text, None).is_err());
+        assert!(recorded.lock().unwrap().notifications.is_empty());
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("short"), "short");
+        let long = "ä".repeat(PREVIEW_LENGTH + 1);
+        assert_eq!(preview(&long).chars().count(), PREVIEW_LENGTH + 1);
+        assert!(preview(&long).ends_with('…'));
+    }
+}
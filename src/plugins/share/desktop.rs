@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use log::warn;
+
+use crate::notification;
+
+/// The parts of the desktop a share touches, so tests can record them instead.
+pub trait Desktop: Send + Sync {
+    fn set_clipboard(&self, text: &str) -> Result<(), String>;
+    /// Starts a program without waiting for it.
+    fn spawn(&self, command: &[String]) -> Result<(), String>;
+    /// Tells the user what happened.
+    fn notify(&self, summary: &str, body: &str);
+}
+
+/// Uses the clipboard tools of the system and desktop notifications.
+pub struct SystemDesktop;
+
+/// Programs that read the text to copy from stdin, in the order they're tried.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn clipboard_commands() -> Vec<Vec<&'static str>> {
+    let mut commands = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(vec!["wl-copy"]);
+    }
+    commands.push(vec!["xclip", "-selection", "clipboard"]);
+    commands.push(vec!["xsel", "--clipboard", "--input"]);
+    commands
+}
+
+#[cfg(target_os = "macos")]
+fn clipboard_commands() -> Vec<Vec<&'static str>> {
+    vec![vec!["pbcopy"]]
+}
+
+#[cfg(not(windows))]
+fn pipe_to(command: &[&str], text: &str) -> io::Result<bool> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    // the X11 and Wayland tools fork to keep serving the clipboard, so this doesn't block
+    Ok(child.wait()?.success())
+}
+
+#[cfg(not(windows))]
+fn set_system_clipboard(text: &str) -> Result<(), String> {
+    for command in clipboard_commands() {
+        match pipe_to(&command, text) {
+            Ok(true) => return Ok(()),
+            Ok(false) => warn!("{} failed", command[0]),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => warn!("Cannot run {}: {}", command[0], err),
+        }
+    }
+    Err(String::from("No clipboard tool available"))
+}
+
+/// Puts the text on the clipboard as UTF-16, `clip` would read it in the console's code page
+/// and mangle everything that isn't ASCII.
+#[cfg(windows)]
+fn set_system_clipboard(text: &str) -> Result<(), String> {
+    use std::ffi::OsStr;
+    use std::iter;
+    use std::os::windows::ffi::OsStrExt;
+    use std::{mem, ptr};
+    use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use winapi::um::winuser::{
+        CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData, CF_UNICODETEXT,
+    };
+
+    let wide = OsStr::new(text)
+        .encode_wide()
+        .chain(iter::once(0))
+        .collect::<Vec<u16>>();
+    let error = |action: &str| format!("Cannot {}: {}", action, io::Error::last_os_error());
+    unsafe {
+        if OpenClipboard(ptr::null_mut()) == 0 {
+            return Err(error("open the clipboard"));
+        }
+        let result = (|| {
+            if EmptyClipboard() == 0 {
+                return Err(error("empty the clipboard"));
+            }
+            let memory = GlobalAlloc(GMEM_MOVEABLE, wide.len() * mem::size_of::<u16>());
+            if memory.is_null() {
+                return Err(error("allocate clipboard memory"));
+            }
+            let target = GlobalLock(memory) as *mut u16;
+            if target.is_null() {
+                GlobalFree(memory);
+                return Err(error("lock clipboard memory"));
+            }
+            ptr::copy_nonoverlapping(wide.as_ptr(), target, wide.len());
+            GlobalUnlock(memory);
+            // the clipboard owns the memory once this succeeded
+            if SetClipboardData(CF_UNICODETEXT, memory).is_null() {
+                GlobalFree(memory);
+                return Err(error("set the clipboard"));
+            }
+            Ok(())
+        })();
+        CloseClipboard();
+        result
+    }
+}
+
+impl Desktop for SystemDesktop {
+    fn set_clipboard(&self, text: &str) -> Result<(), String> {
+        set_system_clipboard(text)
+    }
+
+    fn spawn(&self, command: &[String]) -> Result<(), String> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| String::from("Empty command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .spawn()
+            .map_err(|err| format!("Cannot run {}: {}", program, err))?;
+        // reap it without blocking the UI
+        let program = program.clone();
+        thread::spawn(move || match child.wait() {
+            Ok(status) if !status.success() => warn!("{} exited with {}", program, status),
+            Err(err) => warn!("Cannot wait for {}: {}", program, err),
+            _ => (),
+        });
+        Ok(())
+    }
+
+    fn notify(&self, summary: &str, body: &str) {
+        notification::show(summary, body);
+    }
+}
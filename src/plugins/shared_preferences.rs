@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{json_store::JsonStore, DecodeError};
+use crate::{logging::PluginCallScope, profile::Profile};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, trace};
+use serde_derive::{Deserialize, Serialize};
+
+const CHANNEL_NAME: &str = "plugins.flutter.io/shared_preferences";
+const STORAGE_FILE_NAME: &str = "shared_preferences.json";
+/// The Dart side prefixes every key with this, like the Android implementation we only hand out
+/// and clear keys that have it.
+const KEY_PREFIX: &str = "flutter.";
+
+plugin_args! {
+    KeyArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+}
+plugin_args! {
+    SetBoolArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+    value: bool, "value", Value::Boolean(v) => v;
+}
+plugin_args! {
+    SetIntArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+    value: Value, "value", v @ Value::I32(_) => v, v @ Value::I64(_) => v;
+}
+plugin_args! {
+    SetDoubleArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+    value: f64, "value", Value::F64(v) => v;
+}
+plugin_args! {
+    SetStringArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+    value: str, "value", Value::String(v) => v.as_str();
+}
+plugin_args! {
+    SetStringListArgs,
+    key: str, "key", Value::String(v) => v.as_str();
+    value: Vec<Value>, "value", Value::List(v) => v;
+}
+
+/// A stored value, tagged with its type so ints don't come back as doubles and so on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Preference {
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    StringList(Vec<String>),
+}
+
+impl Preference {
+    fn to_value(&self) -> Value {
+        match self {
+            Preference::Bool(value) => Value::Boolean(*value),
+            Preference::Int(value) => Value::I64(*value),
+            Preference::Double(value) => Value::F64(*value),
+            Preference::String(value) => Value::String(value.clone()),
+            Preference::StringList(list) => {
+                Value::List(list.iter().cloned().map(Value::String).collect())
+            }
+        }
+    }
+}
+
+pub struct SharedPreferencesPlugin {
+    channel: StandardMethodChannel,
+    preferences: HashMap<String, Preference>,
+    store: JsonStore,
+}
+
+fn invalid_args() -> MethodCallResult<Value> {
+    error_result(String::from(""))
+}
+
+fn error_result(message: String) -> MethodCallResult<Value> {
+    MethodCallResult::Err {
+        details: Value::Null,
+        code: String::from(""),
+        message,
+    }
+}
+
+impl SharedPreferencesPlugin {
+    pub fn new(profile: &Profile) -> Self {
+        Self::with_store(JsonStore::new(profile, STORAGE_FILE_NAME))
+    }
+
+    fn with_store(store: JsonStore) -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+            preferences: store.load(),
+            store,
+        }
+    }
+
+    fn save(&self) -> MethodCallResult<Value> {
+        match self.store.save(&self.preferences) {
+            Ok(()) => MethodCallResult::Ok(Value::Boolean(true)),
+            Err(err) => error_result(format!("Cannot save preferences: {}", err)),
+        }
+    }
+
+    fn get_all(&self) -> MethodCallResult<Value> {
+        trace!("Get all");
+        let map = self
+            .preferences
+            .iter()
+            .filter(|(key, _)| key.starts_with(KEY_PREFIX))
+            .map(|(key, value)| (Value::String(key.clone()), value.to_value()))
+            .collect();
+        MethodCallResult::Ok(Value::Map(map))
+    }
+
+    fn set(&mut self, key: &str, value: Preference) -> MethodCallResult<Value> {
+        trace!("Set key {}. New value: {:?}", key, value);
+        self.preferences.insert(String::from(key), value);
+        self.save()
+    }
+
+    fn remove(&mut self, args: &KeyArgs) -> MethodCallResult<Value> {
+        trace!("Remove key {}", args.key);
+        self.preferences.remove(args.key);
+        self.save()
+    }
+
+    fn clear(&mut self) -> MethodCallResult<Value> {
+        trace!("Clear");
+        self.preferences
+            .retain(|key, _| !key.starts_with(KEY_PREFIX));
+        self.save()
+    }
+
+    fn handle_call(&mut self, method: &str, args: &Value) -> Option<MethodCallResult<Value>> {
+        let response = match method {
+            "getAll" => self.get_all(),
+            "setBool" => match SetBoolArgs::from_value(args) {
+                Ok(args) => self.set(args.key, Preference::Bool(*args.value)),
+                Err(_) => invalid_args(),
+            },
+            "setInt" => match SetIntArgs::from_value(args) {
+                Ok(args) => {
+                    let value = match args.value {
+                        Value::I32(value) => i64::from(*value),
+                        Value::I64(value) => *value,
+                        _ => unreachable!(),
+                    };
+                    self.set(args.key, Preference::Int(value))
+                }
+                Err(_) => invalid_args(),
+            },
+            "setDouble" => match SetDoubleArgs::from_value(args) {
+                // JSON has no NaN or infinity, serde_json would store null and the file wouldn't
+                // load again
+                Ok(args) if !args.value.is_finite() => {
+                    error_result(format!("Cannot store {} in {}", args.value, args.key))
+                }
+                Ok(args) => self.set(args.key, Preference::Double(*args.value)),
+                Err(_) => invalid_args(),
+            },
+            "setString" => match SetStringArgs::from_value(args) {
+                Ok(args) => self.set(args.key, Preference::String(String::from(args.value))),
+                Err(_) => invalid_args(),
+            },
+            "setStringList" => match SetStringListArgs::from_value(args) {
+                Ok(args) => {
+                    let list = args
+                        .value
+                        .iter()
+                        .map(|value| match value {
+                            Value::String(value) => Some(value.clone()),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    match list {
+                        Some(list) => self.set(args.key, Preference::StringList(list)),
+                        None => invalid_args(),
+                    }
+                }
+                Err(_) => invalid_args(),
+            },
+            "remove" => match KeyArgs::from_value(args) {
+                Ok(args) => self.remove(&args),
+                Err(_) => invalid_args(),
+            },
+            "clear" => self.clear(),
+            _ => return None,
+        };
+        Some(response)
+    }
+}
+
+impl Plugin for SharedPreferencesPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        if let Some(response) = self.handle_call(&decoded.method, &decoded.args) {
+            self.channel
+                .send_method_call_response(msg.response_handle, response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonStore, SharedPreferencesPlugin};
+    use crate::testing::TempDir;
+    use flutter_engine::codec::{standard_codec::Value, MethodCallResult};
+    use std::collections::HashMap;
+
+    fn args(key: &str, value: Value) -> Value {
+        let mut map = HashMap::new();
+        map.insert(
+            Value::String(String::from("key")),
+            Value::String(String::from(key)),
+        );
+        map.insert(Value::String(String::from("value")), value);
+        Value::Map(map)
+    }
+
+    fn get_all(plugin: &mut SharedPreferencesPlugin) -> HashMap<Value, Value> {
+        match plugin.handle_call("getAll", &Value::Null) {
+            Some(MethodCallResult::Ok(Value::Map(map))) => map,
+            _ => panic!("getAll didn't return a map"),
+        }
+    }
+
+    fn get(map: &HashMap<Value, Value>, key: &str) -> Option<Value> {
+        map.get(&Value::String(String::from(key))).cloned()
+    }
+
+    #[test]
+    fn test_types_survive_reload() {
+        let dir = TempDir::new("prefs");
+        let path = dir.join("shared_preferences.json");
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(path.clone()));
+
+        let list = Value::List(vec![Value::String(String::from("a"))]);
+        for (method, key, value) in vec![
+            ("setBool", "flutter.bool", Value::Boolean(true)),
+            ("setInt", "flutter.int", Value::I32(3)),
+            ("setInt", "flutter.long", Value::I64(1 << 40)),
+            ("setDouble", "flutter.double", Value::F64(3.0)),
+            (
+                "setString",
+                "flutter.string",
+                Value::String(String::from("3")),
+            ),
+            ("setStringList", "flutter.list", list.clone()),
+            ("setString", "other", Value::String(String::from("hidden"))),
+        ] {
+            match plugin.handle_call(method, &args(key, value)) {
+                Some(MethodCallResult::Ok(Value::Boolean(true))) => (),
+                _ => panic!("{} failed", method),
+            }
+        }
+        assert!(plugin
+            .handle_call(
+                "setStringList",
+                &args("flutter.bad", Value::List(vec![Value::I32(1)]))
+            )
+            .map_or(false, |result| match result {
+                MethodCallResult::Err { .. } => true,
+                _ => false,
+            }));
+
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(path.clone()));
+        let map = get_all(&mut plugin);
+        assert_eq!(map.len(), 6);
+        assert_eq!(get(&map, "flutter.bool"), Some(Value::Boolean(true)));
+        assert_eq!(get(&map, "flutter.int"), Some(Value::I64(3)));
+        assert_eq!(get(&map, "flutter.long"), Some(Value::I64(1 << 40)));
+        assert_eq!(get(&map, "flutter.double"), Some(Value::F64(3.0)));
+        assert_eq!(
+            get(&map, "flutter.string"),
+            Some(Value::String(String::from("3")))
+        );
+        assert_eq!(get(&map, "flutter.list"), Some(list));
+
+        let mut key = HashMap::new();
+        key.insert(
+            Value::String(String::from("key")),
+            Value::String(String::from("flutter.int")),
+        );
+        plugin.handle_call("remove", &Value::Map(key));
+        assert_eq!(get_all(&mut plugin).len(), 5);
+
+        plugin.handle_call("clear", &Value::Null);
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(path));
+        assert!(get_all(&mut plugin).is_empty());
+        assert_eq!(plugin.preferences.len(), 1);
+    }
+
+    #[test]
+    fn test_non_finite_doubles_are_rejected() {
+        let dir = TempDir::new("prefs-nan");
+        let path = dir.join("shared_preferences.json");
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(path.clone()));
+
+        let kept = Value::String(String::from("kept"));
+        plugin.handle_call("setString", &args("flutter.string", kept.clone()));
+        for value in vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            match plugin.handle_call("setDouble", &args("flutter.double", Value::F64(value))) {
+                Some(MethodCallResult::Err { .. }) => (),
+                _ => panic!("{} was accepted", value),
+            }
+        }
+
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(path));
+        let map = get_all(&mut plugin);
+        assert_eq!(map.len(), 1);
+        assert_eq!(get(&map, "flutter.string"), Some(kept));
+    }
+
+    #[test]
+    fn test_save_errors_are_returned() {
+        let dir = TempDir::new("prefs-unwritable");
+        // a file where the store's directory should be makes every save fail
+        std::fs::write(dir.join("file"), "").unwrap();
+        let mut plugin = SharedPreferencesPlugin::with_store(JsonStore::with_path(
+            dir.join("file").join("shared_preferences.json"),
+        ));
+
+        match plugin.handle_call("setBool", &args("flutter.bool", Value::Boolean(true))) {
+            Some(MethodCallResult::Err { message, .. }) => assert!(!message.is_empty()),
+            _ => panic!("setBool didn't report the failed save"),
+        }
+    }
+}
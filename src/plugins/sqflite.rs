@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+
+use self::database::SqlError;
+use super::worker::Worker;
+use crate::{logging::PluginCallScope, profile::Profile};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use log::{debug, info};
+use rusqlite::{Connection, OpenFlags};
+
+mod database;
+
+const CHANNEL_NAME: &str = "com.tekartik.sqflite";
+/// The Dart side only turns errors with this code into a `DatabaseException`.
+const ERROR_CODE: &str = "sqlite_error";
+const IN_MEMORY_PATH: &str = ":memory:";
+
+fn error_result(err: &SqlError) -> MethodCallResult<Value> {
+    MethodCallResult::Err {
+        details: err.details(),
+        code: String::from(ERROR_CODE),
+        message: err.message.clone(),
+    }
+}
+
+fn response(result: Result<Value, SqlError>) -> MethodCallResult<Value> {
+    match result {
+        Ok(value) => MethodCallResult::Ok(value),
+        Err(err) => error_result(&err),
+    }
+}
+
+/// An open database. Statements run on its own thread, so a slow query doesn't block the UI or
+/// other databases.
+struct Database {
+    path: PathBuf,
+    connection: Arc<Mutex<Connection>>,
+    worker: Worker,
+}
+
+/// Stores databases for `sqflite` with SQLite. The app passes absolute paths, normally below
+/// `getDatabasesPath`, which is in the profile's data directory.
+pub struct SqflitePlugin {
+    channel: Arc<StandardMethodChannel>,
+    databases_path: Option<PathBuf>,
+    databases: HashMap<i32, Database>,
+    next_id: i32,
+}
+
+fn int(args: &Value, key: &str) -> Option<i64> {
+    match super::map_value(args, key) {
+        Some(Value::I32(number)) => Some(i64::from(*number)),
+        Some(Value::I64(number)) => Some(*number),
+        _ => None,
+    }
+}
+
+fn flag(args: &Value, key: &str) -> bool {
+    match super::map_value(args, key) {
+        Some(Value::Boolean(value)) => *value,
+        _ => false,
+    }
+}
+
+fn path(args: &Value) -> Result<PathBuf, SqlError> {
+    match super::map_value(args, "path") {
+        Some(Value::String(path)) => Ok(PathBuf::from(path)),
+        _ => Err(SqlError::new(String::from("path is missing"))),
+    }
+}
+
+/// Deletes a database file together with the journal files SQLite keeps next to it.
+fn delete_files(path: &Path) -> Result<Value, SqlError> {
+    for suffix in &["", "-wal", "-shm", "-journal"] {
+        let mut file = path.as_os_str().to_os_string();
+        file.push(suffix);
+        let file = PathBuf::from(file);
+        match fs::remove_file(&file) {
+            Ok(()) => info!("Deleted {}", file.display()),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => {
+                return Err(SqlError::new(format!(
+                    "Cannot delete {}: {}",
+                    file.display(),
+                    err
+                )))
+            }
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn open_connection(path: &Path, read_only: bool) -> Result<Connection, SqlError> {
+    let flags = if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                SqlError::new(format!("Cannot create {}: {}", parent.display(), err))
+            })?;
+        }
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+    };
+    Connection::open_with_flags(path, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|err| SqlError::new(format!("open_failed {}: {}", path.display(), err)))
+}
+
+impl SqflitePlugin {
+    pub fn new(profile: &Profile) -> Self {
+        Self::with_databases_path(profile.data_dir().map(|dir| dir.join("databases")))
+    }
+
+    fn with_databases_path(databases_path: Option<PathBuf>) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            databases_path,
+            databases: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    fn open_database(&mut self, args: &Value) -> Result<Value, SqlError> {
+        let path = path(args)?;
+        let in_memory = path == Path::new(IN_MEMORY_PATH);
+        // single instance is the default in Dart, in-memory databases are never shared
+        if flag(args, "singleInstance") && !in_memory {
+            if let Some((id, _)) = self.databases.iter().find(|(_, db)| db.path == path) {
+                debug!("Reusing database {} for {}", id, path.display());
+                return Ok(Value::I32(*id));
+            }
+        }
+
+        let connection = if in_memory {
+            Connection::open_in_memory().map_err(|err| SqlError::new(err.to_string()))?
+        } else {
+            open_connection(&path, flag(args, "readOnly"))?
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        info!("Opened database {} at {}", id, path.display());
+        self.databases.insert(
+            id,
+            Database {
+                path,
+                connection: Arc::new(Mutex::new(connection)),
+                worker: Worker::new(&format!("sqflite-{}", id)),
+            },
+        );
+        Ok(Value::I32(id))
+    }
+
+    /// Closes the connections to the database, then deletes it on the thread of the last one, so
+    /// calls that are still queued for it can finish first.
+    fn delete_database<F>(&mut self, args: &Value, respond: F)
+    where
+        F: FnOnce(MethodCallResult<Value>) + Send + 'static,
+    {
+        let path = match path(args) {
+            Ok(path) => path,
+            Err(err) => {
+                respond(error_result(&err));
+                return;
+            }
+        };
+        let ids: Vec<i32> = self
+            .databases
+            .iter()
+            .filter(|(_, db)| db.path == path)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut open: Vec<Database> = ids
+            .iter()
+            .filter_map(|id| self.databases.remove(id))
+            .collect();
+        let last = match open.pop() {
+            Some(database) => database,
+            None => {
+                respond(response(delete_files(&path)));
+                return;
+            }
+        };
+
+        // each thread drops its connection after its queued calls and exits
+        let (closed, all_closed) = mpsc::channel();
+        for Database {
+            connection, worker, ..
+        } in open
+        {
+            let closed = closed.clone();
+            worker.run(CHANNEL_NAME, "deleteDatabase", move || {
+                drop(connection);
+                closed.send(()).ok();
+            });
+        }
+        drop(closed);
+        let Database {
+            connection, worker, ..
+        } = last;
+        worker.run(CHANNEL_NAME, "deleteDatabase", move || {
+            drop(connection);
+            // ends once the other threads dropped their senders
+            for _ in all_closed {}
+            respond(response(delete_files(&path)));
+        });
+    }
+
+    /// Handles the calls that don't touch an open database right away.
+    fn handle_call(&mut self, method: &str, args: &Value) -> Option<Result<Value, SqlError>> {
+        Some(match method {
+            "getDatabasesPath" => match &self.databases_path {
+                Some(path) => Ok(Value::String(path.to_string_lossy().into_owned())),
+                None => Err(SqlError::new(String::from("No data directory"))),
+            },
+            "openDatabase" => self.open_database(args),
+            "databaseExists" => path(args).map(|path| Value::Boolean(path.is_file())),
+            // there's no native logging to configure
+            "options" | "debugMode" => Ok(Value::Null),
+            _ => return None,
+        })
+    }
+
+    /// Queues a call that has to wait for a database thread, `respond` is called once it's done.
+    fn queue_call<F>(&mut self, method: &str, args: Value, respond: F)
+    where
+        F: FnOnce(MethodCallResult<Value>) + Send + 'static,
+    {
+        match method {
+            "deleteDatabase" => self.delete_database(&args, respond),
+            _ => self.run_on_database(method, args, respond),
+        }
+    }
+
+    /// Queues a call on the database's thread, which calls `respond` once it's done.
+    fn run_on_database<F>(&mut self, method: &str, args: Value, respond: F)
+    where
+        F: FnOnce(MethodCallResult<Value>) + Send + 'static,
+    {
+        let id = int(&args, "id").unwrap_or(0) as i32;
+        let database = match self.databases.get(&id) {
+            Some(database) => database,
+            None => {
+                respond(error_result(&SqlError::new(format!(
+                    "database_closed {}",
+                    id
+                ))));
+                return;
+            }
+        };
+        let connection = database.connection.clone();
+        let job_method = String::from(method);
+        database.worker.run(CHANNEL_NAME, method, move || {
+            let connection = match connection.lock() {
+                Ok(connection) => connection,
+                Err(_) => {
+                    respond(error_result(&SqlError::new(format!(
+                        "Database {} is unusable after a call panicked",
+                        id
+                    ))));
+                    return;
+                }
+            };
+            respond(response(run_statement(&connection, &job_method, &args)));
+        });
+
+        if method == "closeDatabase" {
+            // the thread finishes the queued calls before the connection is dropped
+            self.databases.remove(&id);
+            info!("Closed database {}", id);
+        }
+    }
+}
+
+/// Runs a call for an open database on its thread.
+fn run_statement(connection: &Connection, method: &str, args: &Value) -> Result<Value, SqlError> {
+    match method {
+        "closeDatabase" => Ok(Value::Null),
+        "batch" => match super::map_value(args, "operations") {
+            Some(Value::List(operations)) => database::batch(
+                connection,
+                operations,
+                flag(args, "noResult"),
+                flag(args, "continueOnError"),
+            ),
+            _ => Err(SqlError::new(String::from("operations are missing"))),
+        },
+        _ => {
+            let sql = match super::map_value(args, "sql") {
+                Some(Value::String(sql)) => sql,
+                _ => return Err(SqlError::new(String::from("sql is missing"))),
+            };
+            let arguments =
+                database::arguments(super::map_value(args, "arguments")).map_err(SqlError::new)?;
+            database::run(connection, method, sql, arguments)
+        }
+    }
+}
+
+impl Plugin for SqflitePlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        if let Some(result) = self.handle_call(&decoded.method, &decoded.args) {
+            self.channel
+                .send_method_call_response(msg.response_handle, response(result));
+            return;
+        }
+        match decoded.method.as_str() {
+            "closeDatabase" | "deleteDatabase" | "execute" | "query" | "insert" | "update"
+            | "batch" => {
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                self.queue_call(&decoded.method, decoded.args, move |response| {
+                    channel.send_method_call_response(response_handle, response)
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqflitePlugin;
+    use crate::testing::TempDir;
+    use flutter_engine::codec::{standard_codec::Value, MethodCallResult};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::mpsc;
+
+    fn args(args: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            args.into_iter()
+                .map(|(key, value)| (Value::String(String::from(key)), value))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    /// Runs the call like `handle` does and waits for the database thread to respond. Errors
+    /// are returned as code and message.
+    fn call(
+        plugin: &mut SqflitePlugin,
+        method: &str,
+        args: Value,
+    ) -> Result<Value, (String, String)> {
+        let response = match plugin.handle_call(method, &args) {
+            Some(result) => super::response(result),
+            None => {
+                let (sender, receiver) = mpsc::channel();
+                plugin.queue_call(method, args, move |response| sender.send(response).unwrap());
+                receiver.recv().unwrap()
+            }
+        };
+        match response {
+            MethodCallResult::Ok(value) => Ok(value),
+            MethodCallResult::Err { code, message, .. } => Err((code, message)),
+        }
+    }
+
+    #[test]
+    fn test_database_lifecycle() {
+        let dir = TempDir::new("sqflite");
+        let mut plugin = SqflitePlugin::with_databases_path(Some(dir.to_path_buf()));
+
+        let path = match call(&mut plugin, "getDatabasesPath", Value::Null) {
+            Ok(Value::String(path)) => format!("{}/cache.db", path),
+            _ => panic!("getDatabasesPath failed"),
+        };
+        let open = args(vec![
+            ("path", Value::String(path.clone())),
+            ("singleInstance", Value::Boolean(true)),
+        ]);
+        let id = match call(&mut plugin, "openDatabase", open.clone()) {
+            Ok(Value::I32(id)) => id,
+            _ => panic!("openDatabase failed"),
+        };
+        assert!(fs::metadata(&path).is_ok());
+        assert_eq!(call(&mut plugin, "openDatabase", open), Ok(Value::I32(id)));
+
+        let statement = |sql: &str| {
+            args(vec![
+                ("id", Value::I32(id)),
+                ("sql", Value::String(String::from(sql))),
+                ("arguments", Value::List(vec![])),
+            ])
+        };
+        call(
+            &mut plugin,
+            "execute",
+            statement("CREATE TABLE t (id INTEGER PRIMARY KEY)"),
+        )
+        .unwrap();
+        assert_eq!(
+            call(
+                &mut plugin,
+                "insert",
+                statement("INSERT INTO t DEFAULT VALUES")
+            ),
+            Ok(Value::I64(1))
+        );
+        match call(
+            &mut plugin,
+            "insert",
+            statement("INSERT INTO missing DEFAULT VALUES"),
+        ) {
+            Err((code, message)) => {
+                assert_eq!(code, "sqlite_error");
+                assert!(message.contains("no such table"), "{}", message);
+            }
+            _ => panic!("insert into a missing table worked"),
+        }
+
+        let close = args(vec![("id", Value::I32(id))]);
+        assert_eq!(
+            call(&mut plugin, "closeDatabase", close.clone()),
+            Ok(Value::Null)
+        );
+        match call(&mut plugin, "closeDatabase", close) {
+            Err((_, message)) => assert!(message.starts_with("database_closed")),
+            _ => panic!("closed database is still open"),
+        }
+
+        let open = args(vec![("path", Value::String(path.clone()))]);
+        let journal = format!("{}-journal", path);
+        let path = args(vec![("path", Value::String(path))]);
+        assert_eq!(
+            call(&mut plugin, "databaseExists", path.clone()),
+            Ok(Value::Boolean(true))
+        );
+        // deleting closes databases that are still open and removes their journals
+        let id = match call(&mut plugin, "openDatabase", open) {
+            Ok(Value::I32(id)) => id,
+            _ => panic!("openDatabase failed"),
+        };
+        fs::write(&journal, "").unwrap();
+        call(&mut plugin, "deleteDatabase", path.clone()).unwrap();
+        assert_eq!(
+            call(&mut plugin, "databaseExists", path),
+            Ok(Value::Boolean(false))
+        );
+        assert!(fs::metadata(&journal).is_err());
+        let select = args(vec![
+            ("id", Value::I32(id)),
+            ("sql", Value::String(String::from("SELECT 1"))),
+            ("arguments", Value::List(vec![])),
+        ]);
+        match call(&mut plugin, "execute", select) {
+            Err((_, message)) => assert_eq!(message, format!("database_closed {}", id)),
+            _ => panic!("deleted database is still open"),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use flutter_engine::codec::standard_codec::Value;
+use rusqlite::{
+    types::{Value as SqlValue, ValueRef},
+    Connection,
+};
+
+/// A failed statement, reported to Dart as a `DatabaseException`.
+#[derive(Debug)]
+pub struct SqlError {
+    pub message: String,
+    pub sql: Option<String>,
+    pub arguments: Vec<SqlValue>,
+}
+
+impl SqlError {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            sql: None,
+            arguments: Vec::new(),
+        }
+    }
+
+    /// Error details in the shape the Android plugin sends.
+    pub fn details(&self) -> Value {
+        let mut details = HashMap::new();
+        if let Some(sql) = &self.sql {
+            details.insert(
+                Value::String(String::from("sql")),
+                Value::String(sql.clone()),
+            );
+            details.insert(
+                Value::String(String::from("arguments")),
+                Value::List(self.arguments.iter().map(from_sql_value).collect()),
+            );
+        }
+        Value::Map(details)
+    }
+}
+
+fn to_sql_value(value: &Value) -> Result<SqlValue, String> {
+    Ok(match value {
+        Value::Null => SqlValue::Null,
+        Value::Boolean(value) => SqlValue::Integer(i64::from(*value)),
+        Value::I32(value) => SqlValue::Integer(i64::from(*value)),
+        Value::I64(value) => SqlValue::Integer(*value),
+        Value::F64(value) => SqlValue::Real(*value),
+        Value::String(value) => SqlValue::Text(value.clone()),
+        Value::U8List(value) => SqlValue::Blob(value.clone()),
+        other => return Err(format!("Unsupported argument {:?}", other)),
+    })
+}
+
+fn from_sql_value<'a, V: Into<ValueRef<'a>>>(value: V) -> Value {
+    match value.into() {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(value) => Value::I64(value),
+        ValueRef::Real(value) => Value::F64(value),
+        ValueRef::Text(value) => Value::String(String::from_utf8_lossy(value).into_owned()),
+        ValueRef::Blob(value) => Value::U8List(value.to_vec()),
+    }
+}
+
+/// Converts the `arguments` list of a call, which may be missing.
+pub fn arguments(value: Option<&Value>) -> Result<Vec<SqlValue>, String> {
+    match value {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::List(values)) => values.iter().map(to_sql_value).collect(),
+        Some(_) => Err(String::from("arguments must be a list")),
+    }
+}
+
+fn execute(connection: &Connection, sql: &str, arguments: &[SqlValue]) -> rusqlite::Result<Value> {
+    let mut statement = connection.prepare(sql)?;
+    // step through everything, some statements like PRAGMA return rows
+    let mut rows = statement.query(arguments)?;
+    while rows.next()?.is_some() {}
+    Ok(Value::Null)
+}
+
+fn insert(connection: &Connection, sql: &str, arguments: &[SqlValue]) -> rusqlite::Result<Value> {
+    let changes = connection.prepare(sql)?.execute(arguments)?;
+    // like on Android, nothing inserted (e.g. because of a conflict) gives null
+    Ok(if changes == 0 {
+        Value::Null
+    } else {
+        Value::I64(connection.last_insert_rowid())
+    })
+}
+
+fn update(connection: &Connection, sql: &str, arguments: &[SqlValue]) -> rusqlite::Result<Value> {
+    let changes = connection.prepare(sql)?.execute(arguments)?;
+    Ok(Value::I64(changes as i64))
+}
+
+/// Returns the rows as `{columns: [...], rows: [[...], ...]}`, which the Dart side turns into
+/// maps. That's a lot smaller than sending a map per row.
+fn query(connection: &Connection, sql: &str, arguments: &[SqlValue]) -> rusqlite::Result<Value> {
+    let mut statement = connection.prepare(sql)?;
+    let columns = statement
+        .column_names()
+        .into_iter()
+        .map(|name| Value::String(String::from(name)))
+        .collect::<Vec<_>>();
+    let column_count = columns.len();
+    let mut rows = statement.query(arguments)?;
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        values.push(Value::List(
+            (0..column_count)
+                .map(|i| from_sql_value(row.get_raw(i)))
+                .collect(),
+        ));
+    }
+
+    let mut result = HashMap::new();
+    result.insert(Value::String(String::from("columns")), Value::List(columns));
+    result.insert(Value::String(String::from("rows")), Value::List(values));
+    Ok(Value::Map(result))
+}
+
+/// Runs one of `execute`, `insert`, `update` or `query`.
+pub fn run(
+    connection: &Connection,
+    method: &str,
+    sql: &str,
+    arguments: Vec<SqlValue>,
+) -> Result<Value, SqlError> {
+    let result = match method {
+        "execute" => execute(connection, sql, &arguments),
+        "insert" => insert(connection, sql, &arguments),
+        "update" => update(connection, sql, &arguments),
+        "query" => query(connection, sql, &arguments),
+        _ => return Err(SqlError::new(format!("Unknown method {}", method))),
+    };
+    result.map_err(|err| SqlError {
+        message: err.to_string(),
+        sql: Some(String::from(sql)),
+        arguments,
+    })
+}
+
+fn string_value(map: &Value, key: &str) -> Option<String> {
+    match super::super::map_value(map, key) {
+        Some(Value::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Runs the operations of a batch. With `continue_on_error`, failed operations give an error
+/// entry instead of stopping the batch. The Dart side wraps batches in a transaction already.
+pub fn batch(
+    connection: &Connection,
+    operations: &[Value],
+    no_result: bool,
+    continue_on_error: bool,
+) -> Result<Value, SqlError> {
+    let mut results = Vec::new();
+    for operation in operations {
+        let result = match (
+            string_value(operation, "method"),
+            string_value(operation, "sql"),
+        ) {
+            (Some(method), Some(sql)) => arguments(super::super::map_value(operation, "arguments"))
+                .map_err(SqlError::new)
+                .and_then(|arguments| run(connection, &method, &sql, arguments)),
+            _ => Err(SqlError::new(String::from("method or sql is missing"))),
+        };
+        let entry = match result {
+            Ok(value) => ("result", value),
+            Err(err) if continue_on_error => {
+                let mut error = HashMap::new();
+                error.insert(
+                    Value::String(String::from("code")),
+                    Value::String(String::from(super::ERROR_CODE)),
+                );
+                error.insert(
+                    Value::String(String::from("message")),
+                    Value::String(err.message.clone()),
+                );
+                error.insert(Value::String(String::from("data")), err.details());
+                ("error", Value::Map(error))
+            }
+            Err(err) => return Err(err),
+        };
+        let mut map = HashMap::new();
+        map.insert(Value::String(String::from(entry.0)), entry.1);
+        results.push(Value::Map(map));
+    }
+    Ok(if no_result {
+        Value::Null
+    } else {
+        Value::List(results)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arguments, batch, run};
+    use flutter_engine::codec::standard_codec::Value;
+    use rusqlite::Connection;
+    use std::collections::HashMap;
+
+    fn string(value: &str) -> Value {
+        Value::String(String::from(value))
+    }
+
+    fn map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (string(key), value))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    fn call(connection: &Connection, method: &str, sql: &str, args: Vec<Value>) -> Value {
+        let args = arguments(Some(&Value::List(args))).unwrap();
+        run(connection, method, sql, args).unwrap()
+    }
+
+    fn rows(result: Value) -> Vec<Value> {
+        match result {
+            Value::Map(mut result) => match result.remove(&string("rows")) {
+                Some(Value::List(rows)) => rows,
+                _ => panic!("no rows"),
+            },
+            _ => panic!("not a query result"),
+        }
+    }
+
+    #[test]
+    fn test_types_and_statements() {
+        let connection = Connection::open_in_memory().unwrap();
+        call(
+            &connection,
+            "execute",
+            "CREATE TABLE cache (id INTEGER PRIMARY KEY, url TEXT UNIQUE, size REAL, data BLOB)",
+            vec![],
+        );
+        let insert = "INSERT OR IGNORE INTO cache (url, size, data) VALUES (?, ?, ?)";
+        let args = vec![
+            string("https://a"),
+            Value::F64(1.5),
+            Value::U8List(vec![1, 2]),
+        ];
+        assert_eq!(
+            call(&connection, "insert", insert, args.clone()),
+            Value::I64(1)
+        );
+        // ignored because of the unique url
+        assert_eq!(call(&connection, "insert", insert, args), Value::Null);
+        let args = vec![string("https://b"), Value::Null, Value::Null];
+        assert_eq!(call(&connection, "insert", insert, args), Value::I64(2));
+
+        let result = call(
+            &connection,
+            "query",
+            "SELECT id, url, size, data FROM cache WHERE id = ?",
+            vec![Value::I32(1)],
+        );
+        match &result {
+            Value::Map(result) => assert_eq!(
+                result[&string("columns")],
+                Value::List(vec![
+                    string("id"),
+                    string("url"),
+                    string("size"),
+                    string("data")
+                ])
+            ),
+            _ => panic!("not a query result"),
+        }
+        assert_eq!(
+            rows(result),
+            vec![Value::List(vec![
+                Value::I64(1),
+                string("https://a"),
+                Value::F64(1.5),
+                Value::U8List(vec![1, 2]),
+            ])]
+        );
+
+        let update = "UPDATE cache SET size = ? WHERE size IS NULL OR size < ?";
+        assert_eq!(
+            call(
+                &connection,
+                "update",
+                update,
+                vec![Value::I64(3), Value::I32(2)]
+            ),
+            Value::I64(2)
+        );
+
+        // transactions are plain statements from the Dart side
+        call(&connection, "execute", "BEGIN IMMEDIATE", vec![]);
+        call(&connection, "execute", "DELETE FROM cache", vec![]);
+        call(&connection, "execute", "ROLLBACK", vec![]);
+        assert_eq!(
+            rows(call(
+                &connection,
+                "query",
+                "SELECT COUNT(*) FROM cache",
+                vec![]
+            )),
+            vec![Value::List(vec![Value::I64(2)])]
+        );
+
+        let err = run(
+            &connection,
+            "insert",
+            "INSERT INTO cache (url) VALUES (?)",
+            arguments(Some(&Value::List(vec![string("https://a")]))).unwrap(),
+        )
+        .unwrap_err();
+        assert!(
+            err.message.contains("UNIQUE constraint failed"),
+            "{}",
+            err.message
+        );
+        assert_eq!(
+            err.details(),
+            map(vec![
+                ("sql", string("INSERT INTO cache (url) VALUES (?)")),
+                ("arguments", Value::List(vec![string("https://a")])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let connection = Connection::open_in_memory().unwrap();
+        let operation = |method: &str, sql: &str, args: Vec<Value>| {
+            map(vec![
+                ("method", string(method)),
+                ("sql", string(sql)),
+                ("arguments", Value::List(args)),
+            ])
+        };
+        let operations = vec![
+            operation("execute", "CREATE TABLE t (id INTEGER PRIMARY KEY)", vec![]),
+            operation(
+                "insert",
+                "INSERT INTO t (id) VALUES (?)",
+                vec![Value::I32(7)],
+            ),
+            operation(
+                "insert",
+                "INSERT INTO t (id) VALUES (?)",
+                vec![Value::I32(7)],
+            ),
+            operation("query", "SELECT id FROM t", vec![]),
+        ];
+
+        assert!(batch(&connection, &operations, false, false).is_err());
+
+        let connection = Connection::open_in_memory().unwrap();
+        let results = match batch(&connection, &operations, false, true).unwrap() {
+            Value::List(results) => results,
+            _ => panic!("no results"),
+        };
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[1], map(vec![("result", Value::I64(7))]));
+        match &results[2] {
+            Value::Map(entry) => match &entry[&string("error")] {
+                Value::Map(error) => assert_eq!(error[&string("code")], string("sqlite_error")),
+                _ => panic!("no error"),
+            },
+            _ => panic!("not a map"),
+        }
+
+        let connection = Connection::open_in_memory().unwrap();
+        assert_eq!(
+            batch(&connection, &operations[..2], true, false).unwrap(),
+            Value::Null
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use self::decoder::{FfmpegDecoder, FrameDecoder};
+use super::images;
+use super::worker::Worker;
+use crate::{logging::PluginCallScope, profile::Profile};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, PlatformMessage, Plugin, PluginRegistry, Window,
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use log::debug;
+
+mod decoder;
+
+const CHANNEL_NAME: &str = "video_thumbnail";
+const FILE_PREFIX: &str = "video_thumbnail";
+/// Same default as the Dart side.
+const DEFAULT_QUALITY: u8 = 10;
+
+/// Arguments of `data` and `file`.
+struct ThumbnailOptions {
+    video: String,
+    path: Option<PathBuf>,
+    format: ImageFormat,
+    max_width: u32,
+    max_height: u32,
+    time_ms: u64,
+    quality: u8,
+}
+
+fn int(args: &Value, key: &str) -> Option<i64> {
+    match super::map_value(args, key) {
+        Some(Value::I32(number)) => Some(i64::from(*number)),
+        Some(Value::I64(number)) => Some(*number),
+        _ => None,
+    }
+}
+
+impl ThumbnailOptions {
+    fn from_value(args: &Value) -> Option<Self> {
+        let video = match super::map_value(args, "video") {
+            Some(Value::String(video)) => video.clone(),
+            _ => return None,
+        };
+        let size =
+            |key| int(args, key).map_or(0, |size| size.max(0).min(i64::from(u32::MAX)) as u32);
+        Some(Self {
+            video,
+            path: match super::map_value(args, "path") {
+                Some(Value::String(path)) => Some(PathBuf::from(path)),
+                _ => None,
+            },
+            // the order of the Dart ImageFormat enum
+            format: match int(args, "format") {
+                Some(0) => ImageFormat::JPEG,
+                Some(2) => ImageFormat::WEBP,
+                _ => ImageFormat::PNG,
+            },
+            max_width: size("maxw"),
+            max_height: size("maxh"),
+            time_ms: int(args, "timeMs").map_or(0, |time| time.max(0) as u64),
+            quality: int(args, "quality")
+                .filter(|quality| *quality >= 1 && *quality <= 100)
+                .map_or(DEFAULT_QUALITY, |quality| quality as u8),
+        })
+    }
+}
+
+/// Size of the thumbnail like on Android: the frame is scaled to the width and height if both
+/// are set, or to one of them with the same aspect ratio.
+fn thumbnail_size(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let scaled = |size: u32, from: u32, to: u32| {
+        ((f64::from(size) * f64::from(to) / f64::from(from)).round() as u32).max(1)
+    };
+    match (max_width, max_height) {
+        (0, 0) => (width, height),
+        (0, max_height) => (scaled(width, height, max_height), max_height),
+        (max_width, 0) => (max_width, scaled(height, width, max_width)),
+        sizes => sizes,
+    }
+}
+
+fn thumbnail(decoder: &dyn FrameDecoder, options: &ThumbnailOptions) -> Result<Vec<u8>, String> {
+    let frame = decoder.decode_frame(&options.video, options.time_ms)?;
+    let (width, height) = frame.dimensions();
+    let (new_width, new_height) =
+        thumbnail_size(width, height, options.max_width, options.max_height);
+    let frame: DynamicImage = if (new_width, new_height) == (width, height) {
+        frame
+    } else {
+        frame.resize_exact(new_width, new_height, image::FilterType::Lanczos3)
+    };
+    images::encode(&frame, options.format, options.quality)
+}
+
+/// Name of the thumbnail when the app only gives a directory.
+fn file_name(video: &str, format: ImageFormat) -> String {
+    let name = video
+        .split(&['?', '#'][..])
+        .next()
+        .and_then(|path| Path::new(path).file_stem())
+        .map_or_else(
+            || String::from(FILE_PREFIX),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+    format!("{}.{}", name, images::extension(format))
+}
+
+/// Writes the thumbnail to the requested file or directory, or to a new file in `temp_dir`.
+fn write_thumbnail(
+    data: &[u8],
+    options: &ThumbnailOptions,
+    temp_dir: Option<&Path>,
+) -> Result<PathBuf, String> {
+    let write_error = |err| format!("Cannot write thumbnail: {}", err);
+    let path = match &options.path {
+        Some(path) if path.is_dir() => path.join(file_name(&options.video, options.format)),
+        Some(path) => path.clone(),
+        None => {
+            let temp_dir = temp_dir.ok_or_else(|| String::from("No temp directory"))?;
+            let (path, mut file) =
+                images::create_temp_file(temp_dir, FILE_PREFIX, images::extension(options.format))
+                    .map_err(|err| format!("Cannot create file: {}", err))?;
+            file.write_all(data).map_err(write_error)?;
+            return Ok(path);
+        }
+    };
+    fs::write(&path, data).map_err(write_error)?;
+    Ok(path)
+}
+
+/// Creates thumbnails of videos with ffmpeg. Decoding can take a while, especially for URLs, so
+/// it happens on a worker thread.
+pub struct VideoThumbnailPlugin {
+    channel: Arc<StandardMethodChannel>,
+    worker: Worker,
+    decoder: Arc<dyn FrameDecoder>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl VideoThumbnailPlugin {
+    pub fn new(profile: &Profile) -> Self {
+        Self::with_decoder(Arc::new(FfmpegDecoder), profile.temp_dir())
+    }
+
+    fn with_decoder(decoder: Arc<dyn FrameDecoder>, temp_dir: Option<PathBuf>) -> Self {
+        Self {
+            channel: Arc::new(StandardMethodChannel::new(CHANNEL_NAME)),
+            worker: Worker::new("video-thumbnail"),
+            decoder,
+            temp_dir,
+        }
+    }
+}
+
+/// Runs a `data` or `file` call.
+fn handle_call(
+    method: &str,
+    args: &Value,
+    decoder: &dyn FrameDecoder,
+    temp_dir: Option<&Path>,
+) -> Result<Value, String> {
+    let options =
+        ThumbnailOptions::from_value(args).ok_or_else(|| String::from("video is missing"))?;
+    let data = thumbnail(decoder, &options)?;
+    if method == "file" {
+        let path = write_thumbnail(&data, &options, temp_dir)?;
+        Ok(Value::String(path.to_string_lossy().into_owned()))
+    } else {
+        Ok(Value::U8List(data))
+    }
+}
+
+impl Plugin for VideoThumbnailPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        _window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "data" | "file" => {
+                let channel = self.channel.clone();
+                let response_handle = msg.response_handle;
+                let decoder = self.decoder.clone();
+                let temp_dir = self.temp_dir.clone();
+                let method = decoded.method.clone();
+                let args = decoded.args;
+                self.worker.run(CHANNEL_NAME, &decoded.method, move || {
+                    let response = match handle_call(&method, &args, &*decoder, temp_dir.as_deref())
+                    {
+                        Ok(value) => MethodCallResult::Ok(value),
+                        Err(message) => MethodCallResult::Err {
+                            details: Value::Null,
+                            code: String::from(""),
+                            message,
+                        },
+                    };
+                    channel.send_method_call_response(response_handle, response);
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decoder::FrameDecoder;
+    use super::{handle_call, thumbnail_size};
+    use crate::testing::TempDir;
+    use flutter_engine::codec::standard_codec::Value;
+    use image::{DynamicImage, GenericImageView, ImageFormat};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    /// Returns a blank 64x36 frame and records what was asked for.
+    #[derive(Default)]
+    struct FakeDecoder(Mutex<Vec<(String, u64)>>);
+
+    impl FrameDecoder for FakeDecoder {
+        fn decode_frame(&self, video: &str, time_ms: u64) -> Result<DynamicImage, String> {
+            self.0.lock().unwrap().push((String::from(video), time_ms));
+            Ok(DynamicImage::new_rgb8(64, 36))
+        }
+    }
+
+    fn args(args: Vec<(&str, Value)>) -> Value {
+        Value::Map(
+            args.into_iter()
+                .map(|(key, value)| (Value::String(String::from(key)), value))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn test_thumbnail_size() {
+        assert_eq!(thumbnail_size(640, 360, 0, 0), (640, 360));
+        assert_eq!(thumbnail_size(640, 360, 128, 0), (128, 72));
+        assert_eq!(thumbnail_size(640, 360, 0, 90), (160, 90));
+        assert_eq!(thumbnail_size(640, 360, 100, 100), (100, 100));
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let dir = TempDir::new("thumbnail");
+        let decoder = FakeDecoder::default();
+
+        let data = match handle_call(
+            "data",
+            &args(vec![
+                (
+                    "video",
+                    Value::String(String::from("https://example.com/clip.mp4")),
+                ),
+                ("path", Value::Null),
+                ("format", Value::I32(0)),
+                ("maxh", Value::I32(0)),
+                ("maxw", Value::I32(32)),
+                ("timeMs", Value::I32(1500)),
+                ("quality", Value::I32(50)),
+            ]),
+            &decoder,
+            None,
+        ) {
+            Ok(Value::U8List(data)) => data,
+            _ => panic!("data failed"),
+        };
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::JPEG);
+        assert_eq!(
+            image::load_from_memory(&data).unwrap().dimensions(),
+            (32, 18)
+        );
+        assert_eq!(
+            *decoder.0.lock().unwrap(),
+            vec![(String::from("https://example.com/clip.mp4"), 1500)]
+        );
+
+        let file = |path: Value| match handle_call(
+            "file",
+            &args(vec![
+                ("video", Value::String(String::from("/videos/clip.mp4"))),
+                ("path", path),
+                ("format", Value::I32(2)),
+            ]),
+            &decoder,
+            Some(&dir.join("temp")),
+        ) {
+            Ok(Value::String(path)) => PathBuf::from(path),
+            _ => panic!("file failed"),
+        };
+        let in_temp = file(Value::Null);
+        assert!(in_temp.starts_with(dir.join("temp")));
+        assert_eq!(in_temp.extension().unwrap(), "webp");
+        let data = fs::read(&in_temp).unwrap();
+        assert!(data.starts_with(b"RIFF") && &data[8..12] == b"WEBP");
+        assert_eq!(
+            file(Value::String(dir.to_string_lossy().into_owned())),
+            dir.join("clip.webp")
+        );
+        assert!(dir.join("clip.webp").is_file());
+    }
+}
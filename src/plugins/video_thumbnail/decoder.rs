@@ -0,0 +1,197 @@
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
+
+/// Extracts single frames from videos.
+pub trait FrameDecoder: Send + Sync {
+    /// Decodes the frame shown at `time_ms` milliseconds into the video, which is a local path
+    /// or a URL.
+    fn decode_frame(&self, video: &str, time_ms: u64) -> Result<DynamicImage, String>;
+}
+
+/// Runs `ffmpeg` with hardware acceleration turned off, so it works the same on every machine.
+/// The frame is piped back as PNG.
+pub struct FfmpegDecoder;
+
+/// Protocols ffmpeg may open, including the ones that HTTP(S) and HLS playlists are built on.
+const PROTOCOL_WHITELIST: &str = "file,http,https,tcp,tls";
+const ALLOWED_SCHEMES: [&str; 3] = ["file", "http", "https"];
+/// How long ffmpeg waits for a read from the input before it gives up.
+const IO_TIMEOUT: Duration = Duration::from_secs(15);
+/// ffmpeg is killed after this, a server that keeps trickling data never hits `IO_TIMEOUT`.
+const DECODE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Rejects inputs that ffmpeg would open with another protocol, like `concat:` or `subfile:`.
+/// Drive letters of Windows paths aren't taken for schemes.
+fn check_input(video: &str) -> Result<(), String> {
+    // like ffmpeg, take the leading scheme characters for a protocol if a `:` or `,` follows
+    let end = video
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.'))
+        .unwrap_or(video.len());
+    let scheme = &video[..end];
+    let is_scheme = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && video[end..].starts_with(&[':', ','][..]);
+    if is_scheme && !ALLOWED_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+        return Err(format!("Unsupported video source {}", video));
+    }
+    Ok(())
+}
+
+fn ffmpeg_args(video: &str, time_ms: u64) -> Vec<String> {
+    vec![
+        String::from("-nostdin"),
+        String::from("-loglevel"),
+        String::from("error"),
+        String::from("-hwaccel"),
+        String::from("none"),
+        // seeking before the input is fast and still exact since ffmpeg 2.1
+        String::from("-ss"),
+        format!("{}.{:03}", time_ms / 1000, time_ms % 1000),
+        String::from("-protocol_whitelist"),
+        String::from(PROTOCOL_WHITELIST),
+        String::from("-rw_timeout"),
+        IO_TIMEOUT.as_micros().to_string(),
+        String::from("-i"),
+        String::from(video),
+        String::from("-frames:v"),
+        String::from("1"),
+        String::from("-f"),
+        String::from("image2pipe"),
+        String::from("-vcodec"),
+        String::from("png"),
+        String::from("-"),
+    ]
+}
+
+/// Like `Command::output`, but kills the program if it runs longer than `timeout`.
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Cannot run {:?}: {}", command, err))?;
+    // the program blocks once a pipe is full, so they're read while waiting
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            Ok(None) => {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(format!(
+                    "{:?} didn't finish within {} s",
+                    command,
+                    timeout.as_secs()
+                ));
+            }
+            Err(err) => return Err(format!("Cannot wait for {:?}: {}", command, err)),
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut data).ok();
+        }
+        data
+    })
+}
+
+impl FrameDecoder for FfmpegDecoder {
+    fn decode_frame(&self, video: &str, time_ms: u64) -> Result<DynamicImage, String> {
+        check_input(video)?;
+        let output = output_with_timeout(
+            Command::new("ffmpeg").args(ffmpeg_args(video, time_ms)),
+            DECODE_TIMEOUT,
+        )?;
+        if !output.status.success() {
+            return Err(format!(
+                "ffmpeg failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        if output.stdout.is_empty() {
+            return Err(format!("{} has no frame at {} ms", video, time_ms));
+        }
+        image::load_from_memory(&output.stdout)
+            .map_err(|err| format!("Cannot decode frame: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_input, ffmpeg_args, IO_TIMEOUT, PROTOCOL_WHITELIST};
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let args = ffmpeg_args("https://example.com/video.mp4", 61_005);
+        let position = |arg: &str| args.iter().position(|a| a == arg).unwrap();
+        assert_eq!(args[position("-ss") + 1], "61.005");
+        assert_eq!(args[position("-i") + 1], "https://example.com/video.mp4");
+        assert_eq!(args[position("-hwaccel") + 1], "none");
+        assert_eq!(
+            args[position("-protocol_whitelist") + 1],
+            PROTOCOL_WHITELIST
+        );
+        assert_eq!(
+            args[position("-rw_timeout") + 1],
+            IO_TIMEOUT.as_micros().to_string()
+        );
+        // input options have to come before the input
+        assert!(position("-ss") < position("-i"));
+        assert!(position("-protocol_whitelist") < position("-i"));
+        assert!(position("-rw_timeout") < position("-i"));
+        assert_eq!(args.last().unwrap(), "-");
+    }
+
+    #[test]
+    fn test_check_input() {
+        assert!(check_input("/home/user/video.mp4").is_ok());
+        assert!(check_input("C:\\Users\\user\\video.mp4").is_ok());
+        assert!(check_input("file:///home/user/video.mp4").is_ok());
+        assert!(check_input("HTTPS://example.com/video.mp4").is_ok());
+        assert!(check_input("concat:/etc/passwd|video.mp4").is_err());
+        assert!(check_input("subfile,,start,0,end,0,,:/etc/passwd").is_err());
+        assert!(check_input("ftp://example.com/video.mp4").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_with_timeout() {
+        use super::output_with_timeout;
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let output = output_with_timeout(
+            Command::new("sh").arg("-c").arg("echo frame"),
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"frame\n");
+
+        let start = Instant::now();
+        assert!(
+            output_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(200))
+                .is_err()
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
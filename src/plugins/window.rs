@@ -0,0 +1,170 @@
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    logging::PluginCallScope,
+    window_state::{Rect, WindowState},
+};
+
+use flutter_engine::{
+    channel::{Channel, StandardMethodChannel},
+    codec::{standard_codec::Value, MethodCallResult},
+    FlutterEngineInner, Monitor, PlatformMessage, Plugin, PluginRegistry, Window, WindowMode,
+};
+use log::{debug, trace};
+
+const CHANNEL_NAME: &str = "openbook_desktop/window";
+
+/// The engine's window, remembered once its saved state was applied. The engine passes the
+/// same window to every call and only drops it when it shuts down, which lets `main` read the
+/// final geometry after the event loop ended, including moves that never reached Dart.
+#[derive(Clone, Default)]
+pub struct LastWindow(Arc<Mutex<Option<WindowPtr>>>);
+
+struct WindowPtr(NonNull<Window>);
+
+// the pointer is only dereferenced on the platform thread, which owns the window
+unsafe impl Send for WindowPtr {}
+
+impl LastWindow {
+    fn set(&self, window: &mut Window) {
+        *self.0.lock().unwrap() = Some(WindowPtr(NonNull::from(window)));
+    }
+
+    /// Copies the window's geometry into `state`. Must be called on the platform thread before
+    /// the engine is shut down, does nothing if the state was never restored.
+    pub fn capture(&self, state: &mut WindowState) {
+        if let Some(window) = self.0.lock().unwrap().as_ref() {
+            capture(state, unsafe { window.0.as_ref() });
+        }
+    }
+}
+
+pub struct WindowPlugin {
+    channel: StandardMethodChannel,
+    state: Arc<Mutex<WindowState>>,
+    last_window: LastWindow,
+    restored: bool,
+}
+
+fn monitor_rect(monitor: &Monitor) -> Option<Rect> {
+    let (x, y) = monitor.get_pos();
+    monitor.get_video_mode().map(|mode| Rect {
+        x,
+        y,
+        width: mode.width,
+        height: mode.height,
+    })
+}
+
+fn capture(state: &mut WindowState, window: &Window) {
+    state.fullscreen = window.with_window_mode(|mode| match mode {
+        WindowMode::FullScreen(_) => true,
+        WindowMode::Windowed => false,
+    });
+    if state.fullscreen {
+        // keep the windowed geometry so leaving fullscreen after a restart works
+        return;
+    }
+    state.maximized = window.is_maximized();
+    if !state.maximized {
+        let (x, y) = window.get_pos();
+        let (width, height) = window.get_size();
+        state.x = Some(x);
+        state.y = Some(y);
+        state.width = width.max(1) as u32;
+        state.height = height.max(1) as u32;
+    }
+}
+
+impl WindowPlugin {
+    pub fn new(state: Arc<Mutex<WindowState>>, last_window: LastWindow) -> Self {
+        Self {
+            channel: StandardMethodChannel::new(CHANNEL_NAME),
+            state,
+            last_window,
+            restored: false,
+        }
+    }
+
+    fn restore(&mut self, window: &mut Window) -> MethodCallResult<Value> {
+        let mut state = self.state.lock().unwrap();
+        // glfw is Copy, so the window can still be changed while the monitors are borrowed
+        let mut glfw = window.glfw;
+        let monitors = glfw.with_connected_monitors(|_, monitors| {
+            monitors.iter().filter_map(monitor_rect).collect::<Vec<_>>()
+        });
+        state.clamp_to_monitors(&monitors);
+        trace!("Restoring window state {:?}", *state);
+
+        if let Some(rect) = state.rect() {
+            window.set_pos(rect.x, rect.y);
+        }
+        window.set_size(state.width as i32, state.height as i32);
+        if state.fullscreen {
+            let index = state.monitor_index(&monitors).unwrap_or(0);
+            glfw.with_connected_monitors(|_, monitors| {
+                if let Some(monitor) = monitors.get(index) {
+                    if let Some(mode) = monitor.get_video_mode() {
+                        window.set_monitor(
+                            WindowMode::FullScreen(monitor),
+                            0,
+                            0,
+                            mode.width,
+                            mode.height,
+                            Some(mode.refresh_rate),
+                        );
+                    }
+                }
+            });
+        } else if state.maximized {
+            window.maximize();
+        }
+
+        self.restored = true;
+        self.last_window.set(window);
+        MethodCallResult::Ok(Value::Null)
+    }
+}
+
+impl Plugin for WindowPlugin {
+    fn init_channel(&self, registry: &PluginRegistry) -> &str {
+        self.channel.init(registry);
+        CHANNEL_NAME
+    }
+
+    fn handle(
+        &mut self,
+        msg: &PlatformMessage,
+        _engine: Arc<FlutterEngineInner>,
+        window: &mut Window,
+    ) {
+        let decoded = self.channel.decode_method_call(msg);
+        let _call = PluginCallScope::enter(CHANNEL_NAME, &decoded.method);
+
+        debug!(
+            "Got method call {} with args: {}",
+            decoded.method,
+            super::debug_print_args(&decoded.args)
+        );
+        match decoded.method.as_str() {
+            "restore" => {
+                let response = self.restore(window);
+                self.channel
+                    .send_method_call_response(msg.response_handle, response);
+            }
+            "save" => {
+                // only start tracking the window once the saved state has been applied,
+                // otherwise the default geometry would overwrite it
+                if self.restored {
+                    capture(&mut self.state.lock().unwrap(), window);
+                }
+                self.channel.send_method_call_response(
+                    msg.response_handle,
+                    MethodCallResult::Ok(Value::Null),
+                );
+            }
+            _ => (),
+        }
+    }
+}
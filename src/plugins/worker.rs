@@ -0,0 +1,61 @@
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use crate::logging::PluginCallScope;
+
+use log::warn;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A background thread for plugin calls that would stall the UI if they ran on the platform
+/// thread. Jobs run one after another in the order they were queued.
+pub struct Worker {
+    name: String,
+    sender: Option<Sender<Job>>,
+}
+
+impl Worker {
+    pub fn new(name: &str) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let spawned = thread::Builder::new()
+            .name(String::from(name))
+            .spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            });
+        let sender = match spawned {
+            Ok(_) => Some(sender),
+            Err(err) => {
+                warn!("Cannot start {} thread, running jobs inline: {}", name, err);
+                None
+            }
+        };
+        Self {
+            name: String::from(name),
+            sender,
+        }
+    }
+
+    /// Queues the job. Records it logs are tagged with the plugin call like on the platform
+    /// thread.
+    pub fn run<F>(&self, channel: &str, method: &str, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let (channel, method) = (String::from(channel), String::from(method));
+        let job: Job = Box::new(move || {
+            let _call = PluginCallScope::enter(&channel, &method);
+            job();
+        });
+        match &self.sender {
+            Some(sender) => {
+                if let Err(mpsc::SendError(job)) = sender.send(job) {
+                    warn!("The {} thread is gone, running job inline", self.name);
+                    job();
+                }
+            }
+            None => job(),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dbus::{
+    arg::{AppendAll, PropMap, RefArg, Variant},
+    blocking::Connection,
+    message::MatchRule,
+    Error, Message, Path,
+};
+
+const DESTINATION: &str = "org.freedesktop.portal.Desktop";
+const OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Calls an XDG desktop portal method that answers through a `Request` object and blocks until
+/// the user is done, or at most `timeout`. `make_args` gets the options map with the request's
+/// `handle_token` already set and returns the method's arguments. Returns `None` if the user
+/// cancelled.
+pub fn request<A, F>(
+    interface: &str,
+    method: &str,
+    timeout: Duration,
+    make_args: F,
+) -> Result<Option<PropMap>, Error>
+where
+    A: AppendAll,
+    F: FnOnce(PropMap) -> A,
+{
+    let connection = Connection::new_session()?;
+    let token = format!(
+        "openbook_desktop_{}_{}",
+        process::id(),
+        NEXT_TOKEN.fetch_add(1, Ordering::SeqCst)
+    );
+    // the request path is known up front, so we can't miss a quick response
+    let sender = connection
+        .unique_name()
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = Path::from(format!("{}/request/{}/{}", OBJECT_PATH, sender, token));
+
+    let response = Arc::new(Mutex::new(None));
+    let response_slot = response.clone();
+    connection.add_match(
+        MatchRule::new_signal("org.freedesktop.portal.Request", "Response")
+            .with_path(request_path.clone()),
+        move |_: (), _: &Connection, message: &Message| {
+            *response_slot.lock().unwrap() = message.duplicate().ok();
+            false
+        },
+    )?;
+
+    let mut options = PropMap::new();
+    options.insert(
+        String::from("handle_token"),
+        Variant(Box::new(token) as Box<dyn RefArg>),
+    );
+    connection
+        .with_proxy(DESTINATION, OBJECT_PATH, CALL_TIMEOUT)
+        .method_call::<(Path,), _, _, _>(interface, method, make_args(options))?;
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if let Some(message) = response.lock().unwrap().take() {
+            let (code, results) = message.read2::<u32, PropMap>()?;
+            return match code {
+                0 => Ok(Some(results)),
+                1 => Ok(None),
+                _ => Err(Error::new_custom(
+                    "org.freedesktop.portal.Error.Failed",
+                    &format!("{}.{} failed", interface, method),
+                )),
+            };
+        }
+        connection.process(Duration::from_millis(500))?;
+    }
+
+    // the dialog may still be open, nobody would get its result anymore
+    connection
+        .with_proxy(DESTINATION, request_path, CALL_TIMEOUT)
+        .method_call::<(), _, _, _>("org.freedesktop.portal.Request", "Close", ())
+        .ok();
+    Err(Error::new_custom(
+        "org.freedesktop.portal.Error.Failed",
+        &format!("{}.{} timed out", interface, method),
+    ))
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+const DIR_NAME: &str = "openbook";
+
+/// A profile keeps its own set of data, cache, config and state directories. The default profile
+/// uses the plain `openbook` directories, named profiles use `openbook-<name>`.
+#[derive(Clone)]
+pub struct Profile {
+    name: Option<String>,
+}
+
+impl Profile {
+    pub fn new(name: Option<String>) -> Self {
+        Self {
+            name: name.filter(|name| !name.is_empty() && name != "default"),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name.as_str(),
+            None => "default",
+        }
+    }
+
+    fn dir_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}-{}", DIR_NAME, name),
+            None => String::from(DIR_NAME),
+        }
+    }
+
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(self.dir_name()))
+    }
+
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join(self.dir_name()))
+    }
+
+    /// Directory for files handed to the app, like picked images. It's also what path_provider
+    /// reports as temporary directory, so the app can clean it up.
+    pub fn temp_dir(&self) -> Option<PathBuf> {
+        self.cache_dir()
+    }
+
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(self.dir_name()))
+    }
+
+    /// Directory for logs and other state that should survive restarts but isn't user data.
+    /// This is `$XDG_STATE_HOME` on Linux and the cache directory elsewhere.
+    pub fn state_dir(&self) -> Option<PathBuf> {
+        state_home().map(|dir| dir.join(self.dir_name()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn state_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|dir| dir.join(".local").join("state")))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn state_home() -> Option<PathBuf> {
+    dirs::cache_dir()
+}
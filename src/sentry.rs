@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+use log::{debug, info, warn};
+use rand::{thread_rng, RngCore};
+
+/// Sentry requires a public key in the DSN, but it's never checked here.
+const PUBLIC_KEY: &str = "openbook";
+const PROJECT_ID: &str = "1";
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+/// Limit for the request line and headers on top of the body.
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+/// Clients that stall longer than this are dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimal HTTP endpoint on localhost that accepts events from Sentry clients and writes them
+/// to disk instead of sending them anywhere.
+pub struct LocalSentry {
+    addr: SocketAddr,
+}
+
+impl LocalSentry {
+    pub fn start(dir: PathBuf, port: u16) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let addr = listener.local_addr()?;
+        info!(
+            "Capturing Sentry events on {}, writing them to {}",
+            addr,
+            dir.display()
+        );
+        thread::Builder::new()
+            .name(String::from("sentry"))
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("Cannot accept Sentry connection: {}", err);
+                            continue;
+                        }
+                    };
+                    // a slow client mustn't hold up the reports of others
+                    let dir = dir.clone();
+                    let spawned = thread::Builder::new()
+                        .name(String::from("sentry-connection"))
+                        .spawn(move || {
+                            if let Err(err) = handle_connection(stream, &dir) {
+                                warn!("Cannot handle Sentry request: {}", err);
+                            }
+                        });
+                    if let Err(err) = spawned {
+                        warn!("Cannot handle Sentry connection: {}", err);
+                    }
+                }
+            })?;
+        Ok(Self { addr })
+    }
+
+    pub fn dsn(&self) -> String {
+        format!("http://{}@{}/{}", PUBLIC_KEY, self.addr, PROJECT_ID)
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = String::from(parts.next().ok_or_else(|| invalid_data("Missing method"))?);
+    let path = String::from(parts.next().ok_or_else(|| invalid_data("Missing path"))?);
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(index) = header.find(':') {
+            headers.insert(
+                header[..index].trim().to_lowercase(),
+                String::from(header[index + 1..].trim()),
+            );
+        }
+    }
+
+    let mut body = Vec::new();
+    if headers.get("transfer-encoding").map(String::as_str) == Some("chunked") {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| invalid_data("Invalid chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            if body.len() + size > MAX_BODY_SIZE {
+                return Err(invalid_data("Body too large"));
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = headers.get("content-length") {
+        let length = length
+            .parse::<usize>()
+            .map_err(|_| invalid_data("Invalid content length"))?;
+        if length > MAX_BODY_SIZE {
+            return Err(invalid_data("Body too large"));
+        }
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    }
+
+    // the limit applies to the decompressed body as well
+    let limit = MAX_BODY_SIZE as u64 + 1;
+    let mut decoded = Vec::new();
+    match headers.get("content-encoding").map(String::as_str) {
+        Some("gzip") => {
+            GzDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded)?;
+        }
+        Some("deflate") => {
+            DeflateDecoder::new(&body[..])
+                .take(limit)
+                .read_to_end(&mut decoded)?;
+        }
+        _ => decoded = body,
+    }
+    if decoded.len() > MAX_BODY_SIZE {
+        return Err(invalid_data("Body too large"));
+    }
+
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body: decoded,
+    })
+}
+
+fn event_id(body: &[u8]) -> String {
+    serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|event| event["event_id"].as_str().map(String::from))
+        .filter(|id| id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or_else(|| {
+            let mut id = [0u8; 16];
+            thread_rng().fill_bytes(&mut id);
+            id.iter().map(|byte| format!("{:02x}", byte)).collect()
+        })
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn handle_connection(mut stream: TcpStream, dir: &Path) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let reader = stream
+        .try_clone()?
+        .take((MAX_HEADER_SIZE + MAX_BODY_SIZE) as u64);
+    let request = read_request(&mut BufReader::new(reader))?;
+    debug!(
+        "Got Sentry request {} {} ({} bytes, {:?})",
+        request.method,
+        request.path,
+        request.body.len(),
+        request.headers.get("content-type")
+    );
+
+    let path = request.path.split('?').next().unwrap_or("");
+    let kind = if request.method != "POST" {
+        None
+    } else if path.ends_with("/store/") {
+        Some("json")
+    } else if path.ends_with("/envelope/") {
+        Some("envelope")
+    } else {
+        None
+    };
+    let extension = match kind {
+        Some(extension) => extension,
+        None => return write_response(&mut stream, "404 Not Found", "{}"),
+    };
+
+    // envelopes start with a JSON header line that carries the event id
+    let id = match extension {
+        "envelope" => {
+            let header_end = request
+                .body
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .unwrap_or(request.body.len());
+            event_id(&request.body[..header_end])
+        }
+        _ => event_id(&request.body),
+    };
+    let file = dir.join(format!(
+        "{}-{}.{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        id,
+        extension
+    ));
+    fs::write(&file, &request.body)?;
+    info!("Captured Sentry event {} in {}", id, file.display());
+
+    write_response(&mut stream, "200 OK", &format!("{{\"id\":\"{}\"}}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::LocalSentry;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_store_event() {
+        let dir = TempDir::new("sentry");
+        let sentry = LocalSentry::start(dir.to_path_buf(), 0).expect("Cannot start endpoint");
+        let addr = sentry.dsn().rsplit('@').next().unwrap().replace("/1", "");
+
+        // a client that never sends anything doesn't block the others
+        let _stalled = TcpStream::connect(&addr).expect("Cannot connect");
+        let body = r#"{"event_id":"0123456789abcdef0123456789abcdef","message":"test"}"#;
+        let mut stream = TcpStream::connect(addr).expect("Cannot connect");
+        write!(
+            stream,
+            "POST /api/1/store/ HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .expect("Cannot send request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .expect("Cannot read response");
+
+        let files = fs::read_dir(&dir)
+            .expect("Cannot read event dir")
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(r#"{"id":"0123456789abcdef0123456789abcdef"}"#));
+        assert_eq!(files.len(), 1);
+        assert!(files[0]
+            .to_string_lossy()
+            .ends_with("-0123456789abcdef0123456789abcdef.json"));
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use log::{debug, warn};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::profile::Profile;
+
+const STATE_FILE_NAME: &str = "window_state.json";
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 600;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && i64::from(x) < i64::from(self.x) + i64::from(self.width)
+            && i64::from(y) < i64::from(self.y) + i64::from(self.height)
+    }
+
+    fn overlap(&self, other: &Rect) -> u64 {
+        let left = i64::from(self.x.max(other.x));
+        let top = i64::from(self.y.max(other.y));
+        let right = (i64::from(self.x) + i64::from(self.width))
+            .min(i64::from(other.x) + i64::from(other.width));
+        let bottom = (i64::from(self.y) + i64::from(self.height))
+            .min(i64::from(other.y) + i64::from(other.height));
+        if right <= left || bottom <= top {
+            0
+        } else {
+            ((right - left) * (bottom - top)) as u64
+        }
+    }
+}
+
+/// Window geometry as it was when the app was last shut down. The position is unknown until
+/// the window has been saved at least once.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        Self {
+            x: None,
+            y: None,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            maximized: false,
+            fullscreen: false,
+        }
+    }
+}
+
+impl WindowState {
+    fn path(profile: &Profile) -> Option<PathBuf> {
+        profile.data_dir().map(|dir| dir.join(STATE_FILE_NAME))
+    }
+
+    pub fn load(profile: &Profile) -> Self {
+        let state: Self = match Self::path(profile).map(File::open) {
+            Some(Ok(file)) => match serde_json::from_reader(file) {
+                Ok(state) => state,
+                Err(err) => {
+                    warn!("Cannot parse saved window state: {}", err);
+                    Self::default()
+                }
+            },
+            _ => Self::default(),
+        };
+        if state.width == 0 || state.height == 0 {
+            return Self::default();
+        }
+        debug!("Loaded window state {:?}", state);
+        state
+    }
+
+    pub fn save(&self, profile: &Profile) {
+        let path = match Self::path(profile) {
+            Some(path) => path,
+            None => return,
+        };
+        debug!("Saving window state {:?}", self);
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(&path))
+            .map_err(|err| err.to_string())
+            .and_then(|file| serde_json::to_writer(file, self).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("Cannot save window state: {}", err);
+        }
+    }
+
+    pub fn rect(&self) -> Option<Rect> {
+        match (self.x, self.y) {
+            (Some(x), Some(y)) => Some(Rect {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the monitor the window is mostly on, falling back to the first monitor.
+    pub fn monitor_index(&self, monitors: &[Rect]) -> Option<usize> {
+        if monitors.is_empty() {
+            return None;
+        }
+        let rect = match self.rect() {
+            Some(rect) => rect,
+            None => return Some(0),
+        };
+        let center_x = (i64::from(rect.x) + i64::from(rect.width) / 2) as i32;
+        let center_y = (i64::from(rect.y) + i64::from(rect.height) / 2) as i32;
+        if let Some(index) = monitors.iter().position(|m| m.contains(center_x, center_y)) {
+            return Some(index);
+        }
+        monitors
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| (index, monitor.overlap(&rect)))
+            .filter(|(_, overlap)| *overlap > 0)
+            .max_by_key(|(_, overlap)| *overlap)
+            .map(|(index, _)| index)
+            .or(Some(0))
+    }
+
+    /// Moves and shrinks the window so it lies entirely on one of the given monitors. Windows
+    /// that aren't visible on any monitor are centered on the first one.
+    pub fn clamp_to_monitors(&mut self, monitors: &[Rect]) {
+        let index = match self.monitor_index(monitors) {
+            Some(index) => index,
+            None => return,
+        };
+        let monitor = monitors[index];
+        let visible = self.rect().map_or(false, |rect| monitor.overlap(&rect) > 0);
+
+        self.width = self.width.min(monitor.width);
+        self.height = self.height.min(monitor.height);
+        let max_x = i64::from(monitor.x) + i64::from(monitor.width - self.width);
+        let max_y = i64::from(monitor.y) + i64::from(monitor.height - self.height);
+        if visible {
+            let x = i64::from(self.x.unwrap_or(monitor.x));
+            let y = i64::from(self.y.unwrap_or(monitor.y));
+            self.x = Some(x.max(i64::from(monitor.x)).min(max_x) as i32);
+            self.y = Some(y.max(i64::from(monitor.y)).min(max_y) as i32);
+        } else {
+            self.x = Some(((i64::from(monitor.x) + max_x) / 2) as i32);
+            self.y = Some(((i64::from(monitor.y) + max_y) / 2) as i32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rect, WindowState};
+
+    const MONITORS: [Rect; 2] = [
+        Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        },
+        Rect {
+            x: 1920,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        },
+    ];
+
+    fn state(x: i32, y: i32, width: u32, height: u32) -> WindowState {
+        WindowState {
+            x: Some(x),
+            y: Some(y),
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clamp_keeps_visible_window() {
+        let mut window = state(2000, 100, 800, 600);
+        window.clamp_to_monitors(&MONITORS);
+        assert_eq!(window, state(2000, 100, 800, 600));
+    }
+
+    #[test]
+    fn test_clamp_moves_partially_visible_window() {
+        let mut window = state(3000, 900, 800, 600);
+        window.clamp_to_monitors(&MONITORS);
+        assert_eq!(window, state(2400, 424, 800, 600));
+    }
+
+    #[test]
+    fn test_clamp_centers_offscreen_window() {
+        let mut window = state(-5000, -5000, 2560, 600);
+        window.clamp_to_monitors(&MONITORS);
+        assert_eq!(window, state(0, 240, 1920, 600));
+    }
+}